@@ -0,0 +1,96 @@
+//! Compares the heap allocations of `to_lp`, whose `String` is pre-sized from an estimate,
+//! of `to_lp_cow`, whose `String` is sized by a first pass, of `write_lp` into a `String`
+//! growing from empty and of `write_lp` with a reused buffer.
+//!
+//! Run with `cargo bench --bench write_lp`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use influx3_lp::Influx3Lp;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    pub temp: f32,
+    pub hum: f64,
+    pub co: i32,
+    pub weather: String,
+    #[influx3_lp(timestamp)]
+    pub timestamp: i64,
+    #[influx3_lp(tag)]
+    pub room: String,
+}
+
+const POINTS: usize = 100_000;
+
+fn measure(name: &str, mut f: impl FnMut(&SensorData)) {
+    let data = SensorData {
+        temp: 21.0,
+        hum: 35.9,
+        co: 0,
+        weather: String::from("sunny"),
+        timestamp: 1735545600,
+        room: String::from("Kitchen"),
+    };
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..POINTS {
+        f(&data);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!("{:<24} {:>8.1} ns/point {:>6.2} allocations/point",
+             name,
+             elapsed.as_nanos() as f64 / POINTS as f64,
+             allocations as f64 / POINTS as f64);
+}
+
+fn main() {
+    measure("to_lp", |data| {
+        std::hint::black_box(data.to_lp());
+    });
+
+    measure("to_lp_cow", |data| {
+        std::hint::black_box(data.to_lp_cow());
+    });
+
+    measure("write_lp (new String)", |data| {
+        let mut buf = String::new();
+        data.write_lp(&mut buf);
+        std::hint::black_box(buf);
+    });
+
+    let mut buf = String::new();
+    measure("write_lp (reused buf)", |data| {
+        buf.clear();
+        data.write_lp(&mut buf);
+        std::hint::black_box(&buf);
+    });
+}
@@ -1,16 +1,106 @@
 //! Macros for serializing a struct to line protocol string.
 //!
-//! There is only one derive macro: 
+//! There are two derive macros:
 //! * `#[derive(Influx3Lp)]`
-//! 
+//! * `#[derive(FromLp)]`, which reads a struct back from a line and shares the attributes below
+//!
 //! There are three kind of attribute-like macros defined: 
-//! * `#[influx3_lp(table_name = "home")]` which must be applied to struct level
-//! * `#[influx3_lp(timestamp)]` which must be applied to field level
-//! * `#[influx3_lp(tag)]` which must be applied to field level
+//! * `#[influx3_lp(table_name = "home")]` which must be applied to struct level, unless a member
+//!   holds the table name. `#[influx3_lp(table_name = HOME)]` takes it from a `&str` const
+//!   instead, which is checked at compile time and escaped when the line is written
+//! * `#[influx3_lp(timestamp)]` which must be applied to field level, an integer is written as it
+//!   is and a `SystemTime` as nanoseconds since the Unix epoch. `#[influx3_lp(timestamp, precision
+//!   = "ms")]` declares the unit of the timestamp, `"s"`, `"ms"`, `"us"` or the default `"ns"`, a
+//!   `SystemTime` is then written in it and `Influx3Lp::lp_precision()` returns it. A member of
+//!   another type is a compile error
+//! * `#[influx3_lp(tag)]` which must be applied to field level, `#[influx3_lp(tag, name = "host")]`
+//!   changes the tag key, `#[influx3_lp(tag, trim)]` strips leading and trailing whitespace from
+//!   the value and leaves the tag out if nothing remains, `#[influx3_lp(tag, display)]` writes the
+//!   value with its `Display`, for a foreign type like `uuid::Uuid` which can't implement `LpTagValue`
+//! * `#[influx3_lp(field, name = "temp")]` which may be applied to field level to change the field key,
+//!   `field` is optional since it's the default role
+//! * `#[influx3_lp(order_from_env = "HOME_ORDER")]` which may be applied to struct level, tags and
+//!   fields are emitted in the order of the comma separated keys of the `HOME_ORDER` environment
+//!   variable read at compile time
+//! * `#[influx3_lp(rename_all = "camelCase")]` which may be applied to struct level to derive every
+//!   tag and field key without a `name` from the member name, `"snake_case"`, `"PascalCase"` and
+//!   `"kebab-case"` are supported too
+//! * `#[influx3_lp(coalesce(key = "temp", primary, fallback))]` which may be applied to struct level,
+//!   the `Option` fields `primary` and `fallback` are written as the single field `temp` taking the
+//!   first `Some`, `key` defaults to the key of the first field. The `help` and `unit` of the first
+//!   field go to `lp_schema()`, attributes changing how a value is written are rejected
+//! * `#[influx3_lp(field_prefix = "cpu_", field_suffix = "_pct")]` which may be applied to struct
+//!   level to namespace the keys of the fields without their own `name`, after `rename_all`
+//! * `#[influx3_lp(names("x", "y"))]` which must be applied to a tuple struct, giving the keys of
+//!   its members in order. A member with its own `name` keeps it, the key given for the timestamp
+//!   or a skipped member is ignored
+//! * `#[influx3_lp(sort_tags)]` which may be applied to struct level to emit tags sorted by key,
+//!   as InfluxDB recommends, the order is decided at compile time and overrides `order_from_env`
+//!   for tags. The tags of a flattened member or a tags map are only known at runtime, they are
+//!   then sorted with the others when the line is written. Off by default, tags keep their
+//!   declaration order
+//! * `#[influx3_lp(sort_fields)]` which may be applied to struct level to emit fields sorted by
+//!   key, for stable snapshots. A flattened member or a fields map is placed by its member name
+//!   as a whole. Off by default, fields keep their declaration order
+//! * `#[influx3_lp(fields)]` which may be applied to a map member like `HashMap<String, f64>`, its
+//!   entries are written as fields sorted by key, or in insertion order for an `IndexMap` with the
+//!   `indexmap` feature
+//! * `#[influx3_lp(tags)]` which may be applied to a map member like `BTreeMap<String, String>`,
+//!   its entries are written as tags sorted by key, or in insertion order for an `IndexMap`, an
+//!   entry with an empty value is left out
+//! * `#[influx3_lp(flatten)]` which may be applied to a member whose type also derives `Influx3Lp`,
+//!   its tags and fields are inlined into the line, its `table_name` and timestamp are ignored. A
+//!   key written twice, by the member and the struct or a map, is a `DuplicateTagKey` or
+//!   `DuplicateFieldKey` error when the line is written
+//! * `#[influx3_lp(table_name)]` which may be applied to a `String` or `&str` member instead of the
+//!   struct level `table_name`, the measurement is then taken from the member at runtime
+//! * `#[influx3_lp(skip)]` which may be applied to field level to leave the member out
+//! * `#[influx3_lp(explode)]` which may be applied to one `Vec<T>` member of a struct to write a
+//!   line per element, sharing the tags, the other fields and the timestamp. The element is
+//!   written as a field keyed by the member, after the other fields, and lines are separated by
+//!   `\n`. An empty `Vec` is an `EmptyFieldSet` error. A flattened struct's exploded member is
+//!   ignored
+//! * `#[influx3_lp(skip_if = "path::to::fn")]` which may be applied to a field to leave it out
+//!   when the `fn(&FieldType) -> bool` returns true, an `Option` field is left out when it's
+//!   `None` or the predicate, which gets the whole `&Option<T>`, returns true
+//! * `#[influx3_lp(default = 0)]` which may be applied to an `Option` field to write the value
+//!   instead of leaving the field out when it's `None`, `default = "path::to::fn"` calls a
+//!   function instead. The value must have the type inside the `Option`
+//! * `#[influx3_lp(precision = 3)]` which may be applied to a float field to write it with a fixed
+//!   number of decimal places
+//! * `#[influx3_lp(float_style = "explicit")]` which may be applied to a float field to write an
+//!   integral value as `21.0` instead of the default `"plain"` `21`, InfluxDB reads both as a
+//!   float but text parsers may take `21` for an integer
+//! * `#[influx3_lp(duration_unit = "ms")]` which may be applied to a `Duration` field to write it
+//!   as an `i` integer of whole `"ns"`, `"us"`, `"ms"` or `"s"`, rounded down
+//! * `#[influx3_lp(as = "float")]` which may be applied to an integer field to write it as a float,
+//!   to match the type a column already has in InfluxDB. `as = "integer"` writes a number as an
+//!   `i` integer, a float with a fractional part is then an error at runtime. Other types are
+//!   rejected at compile time
+//! * `#[influx3_lp(max_len = 256)]` which may be applied to a string field to change its length
+//!   limit from 64K, `#[influx3_lp(max_field_len = 32768)]` on struct level changes it for every
+//!   string field without its own `max_len`
+//! * `#[influx3_lp(len_unit = "chars")]` which may be applied to a string field or to struct level
+//!   to count the length limit in characters instead of the default `"bytes"`. InfluxDB itself
+//!   limits string fields to 64KB, counted in bytes
+//! * `#[influx3_lp(on_overflow = "truncate")]` which may be applied to a string field to cut a
+//!   value over the length limit to the limit, on a char boundary, instead of the default
+//!   `"error"`
+//! * `#[influx3_lp(no_len_check)]` which may be applied to a string field known to be short, like
+//!   a fixed-format status, to skip its length check, `max_field_len` included. Nothing stops an
+//!   oversize value then, and InfluxDB rejects a line with a string over 64KB
+//! * `#[influx3_lp(bool_format = "short")]` which may be applied to struct level to write bool
+//!   fields, the values of a fields map included, as `t` / `f` instead of the default `"long"`
+//!   `true` / `false`, shrinking wide boolean lines
+//! * `#[influx3_lp(unsigned = "forbid")]` which may be applied to struct level to write unsigned
+//!   fields, the values of a fields map included, as `i` integers for servers without the
+//!   unsigned type, a value above `i64::MAX` is then an error at runtime. The default `"allow"` writes them with the `u` suffix
+//! * `#[influx3_lp(help = "...", unit = "...")]` which may be applied to field level, the
+//!   metadata is only exposed by `lp_schema()`
 //!
 //! Combined together, we can write:
 //!
-//! ```rust
+//! ```ignore
 //! #[derive(Influx3Lp)]
 //! #[influx3_lp(table_name = "home")]
 //! struct SensorData {
@@ -23,6 +113,21 @@
 //! }
 //! ```
 //!
+//! On enums, every variant must have named fields and is written to the measurement given by
+//! its own `#[influx3_lp(table_name = "...")]`, or the one of the enum. Struct level attributes
+//! of the enum apply to every variant.
+//!
+//! Names reserved by InfluxDB (currently `time`) are rejected at compile time as table name,
+//! tag key or field key.
+//! Table names which are empty or start with `_` are rejected too.
+//!
+//! Two members resolving to the same tag or field key, after `name` and `rename_all`, are
+//! rejected too.
+//!
+//! A field holding a collection like `Vec<T>`, a map or a tuple is rejected at compile time with
+//! a hint to `explode`, `fields` or `skip` it. Any other type without `LpFieldValue` is reported
+//! on the member's type.
+//!
 //! Escape is applied according to [line protocol](https://docs.influxdata.com/influxdb3/core/reference/line-protocol/#special-characters).
 
 use proc_macro::TokenStream;
@@ -32,288 +137,1863 @@ use syn::{parse_macro_input, DeriveInput};
 #[proc_macro_derive(Influx3Lp, attributes(influx3_lp))]
 pub fn influx3_lp_macro_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let struct_name = &input.ident;
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    let mut table_name = None;
-    let mut fields = Vec::new();
-    let mut tags = Vec::new();
-    let mut timestamp = None;
+#[proc_macro_derive(FromLp, attributes(influx3_lp))]
+pub fn from_lp_macro_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_lp(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Attributes found on one struct field.
+#[derive(Default)]
+struct FieldAttrs {
+    is_tag: bool,
+    is_timestamp: bool,
+    /// `#[influx3_lp(field)]`, which is also the default role
+    is_field: bool,
+    /// `#[influx3_lp(skip)]`, the member is not serialized at all
+    skip: bool,
+    /// `#[influx3_lp(fields)]`, a map whose entries are written as fields
+    is_fields: bool,
+    /// `#[influx3_lp(tags)]`, a map whose entries are written as tags
+    is_tags: bool,
+    /// `#[influx3_lp(flatten)]`, a member whose tags and fields are inlined
+    flatten: bool,
+    /// `#[influx3_lp(table_name)]`, a member holding the measurement
+    is_table_name: bool,
+    /// key override from `#[influx3_lp(name = "...")]`
+    name: Option<String>,
+    help: Option<String>,
+    unit: Option<String>,
+    /// decimal places of a float field from `#[influx3_lp(precision = 3)]`
+    precision: Option<usize>,
+    /// `Precision` variant of the timestamp from `#[influx3_lp(timestamp, precision = "ms")]`
+    timestamp_precision: Option<&'static str>,
+    /// line protocol type from `#[influx3_lp(as = "float")]`
+    cast: Option<Cast>,
+    /// length limit of a string field from `#[influx3_lp(max_len = 256)]`
+    max_len: Option<usize>,
+    /// `#[influx3_lp(len_unit = "chars")]`
+    len_unit: Option<LenUnit>,
+    /// `#[influx3_lp(on_overflow = "truncate")]`
+    truncate: bool,
+    /// `#[influx3_lp(no_len_check)]`
+    no_len_check: bool,
+    /// `#[influx3_lp(float_style = "explicit")]`
+    explicit_float: bool,
+    /// `#[influx3_lp(duration_unit = "ms")]`, nanoseconds per written unit
+    duration_unit: Option<u128>,
+    /// `#[influx3_lp(tag, trim)]`
+    trim: bool,
+    /// `#[influx3_lp(tag, display)]`, the tag value written with `Display`
+    display: bool,
+    /// `#[influx3_lp(skip_if = "path::to::fn")]`, a predicate leaving the field out
+    skip_if: Option<syn::Path>,
+    /// `#[influx3_lp(default = 0)]` or `default = "path::to::fn"`, the value written for `None`
+    default: Option<proc_macro2::TokenStream>,
+    /// `#[influx3_lp(explode)]`, one line per element of the member
+    explode: bool,
+}
 
-    // struct level attributes 
-    // #[influx3_lp(table_name = "home")]
-    for attr in &input.attrs {
-        if attr.path().is_ident("influx3_lp") {
-            let _ = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident("table_name") {
+impl FieldAttrs {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in &field.attrs {
+            if !attr.path().is_ident("influx3_lp") {
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("timestamp") {
+                    attrs.is_timestamp = true;
+                } else if meta.path.is_ident("tag") {
+                    attrs.is_tag = true;
+                } else if meta.path.is_ident("field") {
+                    attrs.is_field = true;
+                } else if meta.path.is_ident("skip") {
+                    attrs.skip = true;
+                } else if meta.path.is_ident("trim") {
+                    attrs.trim = true;
+                } else if meta.path.is_ident("display") {
+                    attrs.display = true;
+                } else if meta.path.is_ident("explode") {
+                    attrs.explode = true;
+                } else if meta.path.is_ident("default") {
+                    let expr: syn::Expr = meta.value()?.parse()?;
+                    attrs.default = Some(match expr {
+                        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => {
+                            let path: syn::Path = lit.parse()?;
+                            quote! { #path() }
+                        }
+                        expr => quote! { #expr },
+                    });
+                } else if meta.path.is_ident("skip_if") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.skip_if = Some(lit.parse()?);
+                } else if meta.path.is_ident("fields") {
+                    attrs.is_fields = true;
+                } else if meta.path.is_ident("tags") {
+                    attrs.is_tags = true;
+                } else if meta.path.is_ident("flatten") {
+                    attrs.flatten = true;
+                } else if meta.path.is_ident("table_name") {
+                    attrs.is_table_name = true;
+                } else if meta.path.is_ident("name") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    if lit.value().is_empty() {
+                        return Err(syn::Error::new_spanned(lit, "name must not be empty"));
+                    }
+                    attrs.name = Some(lit.value());
+                } else if meta.path.is_ident("help") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.help = Some(lit.value());
+                } else if meta.path.is_ident("unit") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.unit = Some(lit.value());
+                } else if meta.path.is_ident("precision") {
+                    // decimal places of a float, or the unit of the timestamp
+                    match meta.value()?.parse()? {
+                        syn::Lit::Int(lit) => attrs.precision = Some(lit.base10_parse()?),
+                        syn::Lit::Str(lit) => attrs.timestamp_precision = Some(match lit.value().as_str() {
+                            "s" => "Seconds",
+                            "ms" => "Millis",
+                            "us" => "Micros",
+                            "ns" => "Nanos",
+                            _ => return Err(syn::Error::new_spanned(lit, "timestamp precision must be \"s\", \"ms\", \"us\" or \"ns\"")),
+                        }),
+                        lit => return Err(syn::Error::new_spanned(lit, "precision must be a number of decimal places or a timestamp unit")),
+                    }
+                } else if meta.path.is_ident("as") {
                     let lit: syn::LitStr = meta.value()?.parse()?;
-                    table_name = Some(lit.value().escape_table());
+                    attrs.cast = Some(match lit.value().as_str() {
+                        "float" => Cast::Float,
+                        "integer" => Cast::Integer,
+                        _ => return Err(syn::Error::new_spanned(lit, "as must be \"float\" or \"integer\"")),
+                    });
+                } else if meta.path.is_ident("max_len") {
+                    let lit: syn::LitInt = meta.value()?.parse()?;
+                    attrs.max_len = Some(lit.base10_parse()?);
+                } else if meta.path.is_ident("len_unit") {
+                    attrs.len_unit = Some(LenUnit::parse(&meta.value()?.parse()?)?);
+                } else if meta.path.is_ident("on_overflow") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.truncate = match lit.value().as_str() {
+                        "error" => false,
+                        "truncate" => true,
+                        _ => return Err(syn::Error::new_spanned(lit, "on_overflow must be \"error\" or \"truncate\"")),
+                    };
+                } else if meta.path.is_ident("no_len_check") {
+                    attrs.no_len_check = true;
+                } else if meta.path.is_ident("duration_unit") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.duration_unit = Some(match lit.value().as_str() {
+                        "ns" => 1,
+                        "us" => 1_000,
+                        "ms" => 1_000_000,
+                        "s" => 1_000_000_000,
+                        _ => return Err(syn::Error::new_spanned(lit, "duration_unit must be \"ns\", \"us\", \"ms\" or \"s\"")),
+                    });
+                } else if meta.path.is_ident("float_style") {
+                    let lit: syn::LitStr = meta.value()?.parse()?;
+                    attrs.explicit_float = match lit.value().as_str() {
+                        "plain" => false,
+                        "explicit" => true,
+                        _ => return Err(syn::Error::new_spanned(lit, "float_style must be \"plain\" or \"explicit\"")),
+                    };
+                } else {
+                    return Err(meta.error("unknown influx3_lp field attribute"));
                 }
                 Ok(())
-            });
+            })?;
         }
+        // the attributes may be spread over several #[influx3_lp(...)]
+        if [attrs.is_tag, attrs.is_timestamp, attrs.is_field].iter().filter(|role| **role).count() > 1 {
+            let name = field.ident.as_ref().map(ToString::to_string).unwrap_or_default();
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("`{}` can only be one of tag, timestamp or field", name)));
+        }
+        Ok(attrs)
     }
+}
+
+/// Attributes found on the struct.
+#[derive(Default)]
+struct StructAttrs {
+    /// `#[influx3_lp(table_name = "home")]`, unescaped
+    table_name: Option<TableName>,
+    /// keys from `#[influx3_lp(order_from_env = "...")]`
+    order: Option<Vec<String>>,
+    coalesce: Vec<Coalesce>,
+    rename_all: Option<RenameRule>,
+    sort_tags: bool,
+    sort_fields: bool,
+    /// length limit of every string field from `#[influx3_lp(max_field_len = 32768)]`
+    max_field_len: Option<usize>,
+    /// `#[influx3_lp(len_unit = "chars")]` for every string field
+    len_unit: Option<LenUnit>,
+    /// `#[influx3_lp(bool_format = "short")]` for every bool field
+    bool_format: Option<BoolFormat>,
+    /// `#[influx3_lp(unsigned = "forbid")]` for every unsigned field
+    unsigned: Option<Unsigned>,
+    /// `#[influx3_lp(field_prefix = "cpu_")]` / `field_suffix`, around derived field keys
+    field_prefix: Option<String>,
+    field_suffix: Option<String>,
+    /// `#[influx3_lp(names("x", "y"))]`, the keys of the members of a tuple struct
+    names: Option<Vec<syn::LitStr>>,
+}
+
+impl StructAttrs {
+    fn parse(attrs_in: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut attrs = StructAttrs::default();
+        for attr in attrs_in {
+            if attr.path().is_ident("influx3_lp") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("table_name") {
+                        let value: syn::Expr = meta.value()?.parse()?;
+                        if let Some(previous) = &attrs.table_name {
+                            return Err(syn::Error::new_spanned(
+                                &value,
+                                format!("table_name is already set to `{}`, keep only one", previous)));
+                        }
+                        let lit = match value {
+                            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => lit,
+                            syn::Expr::Path(syn::ExprPath { path, qself: None, .. }) => {
+                                attrs.table_name = Some(TableName::Const(path));
+                                return Ok(());
+                            }
+                            other => return Err(syn::Error::new_spanned(
+                                other,
+                                "table_name must be a string literal or the path of a `&str` const")),
+                        };
+                        if is_reserved(&lit.value()) {
+                            return Err(syn::Error::new_spanned(
+                                &lit,
+                                format!("`{}` is reserved by InfluxDB and can't be a table name", lit.value())));
+                        }
+                        if lit.value().is_empty() {
+                            return Err(syn::Error::new_spanned(&lit, "table_name must not be empty"));
+                        }
+                        if lit.value().starts_with('_') {
+                            return Err(syn::Error::new_spanned(
+                                &lit,
+                                "table_name must not start with `_`, which InfluxDB reserves for system tables"));
+                        }
+                        attrs.table_name = Some(TableName::Literal(lit.value()));
+                    } else if meta.path.is_ident("order_from_env") {
+                        let lit: syn::LitStr = meta.value()?.parse()?;
+                        match std::env::var(lit.value()) {
+                            Ok(keys) => {
+                                attrs.order = Some(keys.split(',').map(|k| k.trim().to_string()).collect::<Vec<_>>());
+                            }
+                            Err(_) => {
+                                return Err(syn::Error::new_spanned(
+                                    &lit,
+                                    format!("environment variable `{}` is not set, it should list \
+                                             tag and field keys separated by commas", lit.value())));
+                            }
+                        }
+                    } else if meta.path.is_ident("rename_all") {
+                        let lit: syn::LitStr = meta.value()?.parse()?;
+                        attrs.rename_all = Some(RenameRule::parse(&lit)?);
+                    } else if meta.path.is_ident("sort_tags") {
+                        attrs.sort_tags = true;
+                    } else if meta.path.is_ident("sort_fields") {
+                        attrs.sort_fields = true;
+                    } else if meta.path.is_ident("max_field_len") {
+                        let lit: syn::LitInt = meta.value()?.parse()?;
+                        attrs.max_field_len = Some(lit.base10_parse()?);
+                    } else if meta.path.is_ident("len_unit") {
+                        attrs.len_unit = Some(LenUnit::parse(&meta.value()?.parse()?)?);
+                    } else if meta.path.is_ident("field_prefix") {
+                        let lit: syn::LitStr = meta.value()?.parse()?;
+                        attrs.field_prefix = Some(lit.value());
+                    } else if meta.path.is_ident("field_suffix") {
+                        let lit: syn::LitStr = meta.value()?.parse()?;
+                        attrs.field_suffix = Some(lit.value());
+                    } else if meta.path.is_ident("bool_format") {
+                        attrs.bool_format = Some(BoolFormat::parse(&meta.value()?.parse()?)?);
+                    } else if meta.path.is_ident("names") {
+                        let content;
+                        syn::parenthesized!(content in meta.input);
+                        let names = content.parse_terminated(<syn::LitStr as syn::parse::Parse>::parse, syn::Token![,])?;
+                        attrs.names = Some(names.into_iter().collect());
+                    } else if meta.path.is_ident("unsigned") {
+                        attrs.unsigned = Some(Unsigned::parse(&meta.value()?.parse()?)?);
+                    } else if meta.path.is_ident("coalesce") {
+                        let mut group = Coalesce { key: None, members: Vec::new(), done: false };
+                        meta.parse_nested_meta(|inner| {
+                            if inner.path.is_ident("key") {
+                                let lit: syn::LitStr = inner.value()?.parse()?;
+                                group.key = Some(lit.value());
+                            } else if let Some(member) = inner.path.get_ident() {
+                                group.members.push(member.clone());
+                            } else {
+                                return Err(inner.error("expected a field name"));
+                            }
+                            Ok(())
+                        })?;
+                        if group.members.len() < 2 {
+                            return Err(meta.error("coalesce needs at least two fields"));
+                        }
+                        attrs.coalesce.push(group);
+                    } else {
+                        return Err(meta.error("unknown influx3_lp struct attribute"));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(attrs)
+    }
+
+    /// Attributes of an enum variant, falling back to those of the enum.
+    fn inherit(mut self, parent: &StructAttrs) -> Self {
+        self.table_name = self.table_name.or_else(|| parent.table_name.clone());
+        self.order = self.order.or_else(|| parent.order.clone());
+        self.coalesce.extend(parent.coalesce.iter().cloned());
+        self.rename_all = self.rename_all.or(parent.rename_all);
+        self.sort_tags |= parent.sort_tags;
+        self.sort_fields |= parent.sort_fields;
+        self.max_field_len = self.max_field_len.or(parent.max_field_len);
+        self.len_unit = self.len_unit.or(parent.len_unit);
+        self.bool_format = self.bool_format.or(parent.bool_format);
+        self.unsigned = self.unsigned.or(parent.unsigned);
+        self.field_prefix = self.field_prefix.or_else(|| parent.field_prefix.clone());
+        self.field_suffix = self.field_suffix.or_else(|| parent.field_suffix.clone());
+        self
+    }
+}
+
+/// Wrap the key derived from a field member in `field_prefix` and `field_suffix`.
+fn affix_field_key(key: String, prefix: &Option<String>, suffix: &Option<String>) -> String {
+    format!("{}{}{}", prefix.as_deref().unwrap_or_default(), key, suffix.as_deref().unwrap_or_default())
+}
+
+/// `#[influx3_lp(table_name = ...)]`, a string literal or the path of a `&str` const.
+#[derive(Clone)]
+enum TableName {
+    Literal(String),
+    Const(syn::Path),
+}
+
+impl TableName {
+    /// Length of the measurement as far as it's known at compile time.
+    fn len_hint(&self) -> usize {
+        match self {
+            TableName::Literal(name) => name.len(),
+            TableName::Const(_) => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for TableName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableName::Literal(name) => f.write_str(name),
+            TableName::Const(path) => write!(f, "{}", quote! { #path }),
+        }
+    }
+}
+
+impl quote::ToTokens for TableName {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        match self {
+            TableName::Literal(name) => name.to_tokens(tokens),
+            TableName::Const(path) => path.to_tokens(tokens),
+        }
+    }
+}
+
+/// `#[influx3_lp(coalesce(key = "temp", primary, secondary))]`, several `Option` members
+/// written as one field, taking the first one which is `Some`.
+#[derive(Clone)]
+struct Coalesce {
+    key: Option<String>,
+    members: Vec<syn::Ident>,
+    /// whether the field code was already generated
+    done: bool,
+}
+
+/// `#[influx3_lp(as = "...")]`, the line protocol type a number is written as.
+#[derive(Clone, Copy)]
+enum Cast {
+    Float,
+    Integer,
+}
+
+/// `#[influx3_lp(len_unit = "...")]`, how the length of a string field is counted.
+#[derive(Clone, Copy, PartialEq)]
+enum LenUnit {
+    Bytes,
+    Chars,
+}
+
+impl LenUnit {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "bytes" => Ok(LenUnit::Bytes),
+            "chars" => Ok(LenUnit::Chars),
+            _ => Err(syn::Error::new_spanned(lit, "len_unit must be \"bytes\" or \"chars\"")),
+        }
+    }
+}
+
+/// `#[influx3_lp(bool_format = "...")]`, how bool fields are written.
+#[derive(Clone, Copy, PartialEq)]
+enum BoolFormat {
+    /// `true` / `false`
+    Long,
+    /// `t` / `f`
+    Short,
+}
+
+impl BoolFormat {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "long" => Ok(BoolFormat::Long),
+            "short" => Ok(BoolFormat::Short),
+            _ => Err(syn::Error::new_spanned(lit, "bool_format must be \"long\" or \"short\"")),
+        }
+    }
+}
+
+/// `#[influx3_lp(unsigned = "...")]`, whether unsigned fields may use the `u` type.
+#[derive(Clone, Copy, PartialEq)]
+enum Unsigned {
+    /// written with the `u` suffix
+    Allow,
+    /// written with the `i` suffix, for servers without unsigned fields
+    Forbid,
+}
+
+impl Unsigned {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "allow" => Ok(Unsigned::Allow),
+            "forbid" => Ok(Unsigned::Forbid),
+            _ => Err(syn::Error::new_spanned(lit, "unsigned must be \"allow\" or \"forbid\"")),
+        }
+    }
+}
 
-    // field level attributes 
+/// How the plain fields of a struct are written, from its struct level attributes.
+#[derive(Clone, Copy)]
+struct FieldStyle {
+    short_bool: bool,
+    signed: bool,
+}
+
+impl FieldStyle {
+    fn new(attrs: &StructAttrs) -> Self {
+        FieldStyle {
+            short_bool: attrs.bool_format == Some(BoolFormat::Short),
+            signed: attrs.unsigned == Some(Unsigned::Forbid),
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.short_bool || self.signed
+    }
+}
+
+/// The length limit of a string field, from the field and struct attributes.
+#[derive(Clone, Copy, Default)]
+struct StrLimit {
+    max_len: Option<usize>,
+    len_unit: Option<LenUnit>,
+    truncate: bool,
+    /// no limit at all, not even the 64K default
+    unchecked: bool,
+}
+
+impl StrLimit {
+    fn new(field: StrLimit, attrs: &StructAttrs) -> Self {
+        StrLimit {
+            max_len: if field.unchecked { None } else { field.max_len.or(attrs.max_field_len) },
+            len_unit: if field.unchecked { None } else { field.len_unit.or(attrs.len_unit) },
+            truncate: field.truncate,
+            unchecked: field.unchecked,
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.max_len.is_some() || self.len_unit.is_some() || self.truncate || self.unchecked
+    }
+}
+
+/// Names InfluxDB reserves for itself, they can't be used as table name, tag key or field key.
+///
+/// * `time` is the column holding the timestamp of every point
+/// * `_time` is the name of that column in Flux and InfluxDB 2 exports
+const RESERVED_NAMES: &[&str] = &["time", "_time"];
+
+fn is_reserved(name: &str) -> bool {
+    RESERVED_NAMES.contains(&name)
+}
+
+/// Reject a tag or field key (after renaming) InfluxDB won't accept, pointing at member `ident`.
+fn check_key(key: &str, ident: &syn::Ident) -> syn::Result<()> {
+    // `rename_all` drops underscores, so a member named `__` has no key left
+    if key.is_empty() {
+        return Err(syn::Error::new_spanned(ident, format!("the key of `{}` is empty after renaming", ident)));
+    }
+    if is_reserved(key) {
+        return Err(syn::Error::new_spanned(
+            ident,
+            format!("`{}` is reserved by InfluxDB and can't be a tag or field key", key)));
+    }
+    Ok(())
+}
+
+/// Reject a tag or field key (after renaming) already used by another member, a line
+/// can hold each key only once.
+fn check_unique(key: &str, ident: &syn::Ident, seen: &mut Vec<(String, syn::Ident)>) -> syn::Result<()> {
+    if let Some((_, other)) = seen.iter().find(|(k, _)| k == key) {
+        return Err(syn::Error::new_spanned(
+            ident,
+            format!("key `{}` is already used by `{}`", key, other)));
+    }
+    seen.push((key.to_string(), ident.clone()));
+    Ok(())
+}
+
+/// `#[influx3_lp(rename_all = "...")]`, how keys are derived from snake_case member names.
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Camel,
+    Snake,
+    Pascal,
+    Kebab,
+}
+
+impl RenameRule {
+    fn parse(lit: &syn::LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "camelCase" => Ok(RenameRule::Camel),
+            "snake_case" => Ok(RenameRule::Snake),
+            "PascalCase" => Ok(RenameRule::Pascal),
+            "kebab-case" => Ok(RenameRule::Kebab),
+            _ => Err(syn::Error::new_spanned(
+                lit,
+                "rename_all must be one of \"camelCase\", \"snake_case\", \"PascalCase\", \"kebab-case\"")),
+        }
+    }
+
+    /// The key of member `ident` under `rule`, or its name unchanged without a rule.
+    fn apply(rule: Option<RenameRule>, ident: &syn::Ident) -> String {
+        let name = ident.to_string();
+        let Some(rule) = rule else {
+            return name;
+        };
+
+        let words = name.split('_').filter(|w| !w.is_empty());
+        let capitalize = |w: &str| {
+            let mut chars = w.chars();
+            chars.next()
+                .map(|first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect())
+                .unwrap_or_default()
+        };
+        match rule {
+            RenameRule::Snake => words.map(str::to_lowercase).collect::<Vec<_>>().join("_"),
+            RenameRule::Kebab => words.map(str::to_lowercase).collect::<Vec<_>>().join("-"),
+            RenameRule::Pascal => words.map(capitalize).collect::<Vec<String>>().concat(),
+            RenameRule::Camel => words
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.to_lowercase() } else { capitalize(w) })
+                .collect::<Vec<_>>()
+                .concat(),
+        }
+    }
+}
+
+/// Code generated for the members of a struct, or of one enum variant.
+struct Members {
+    tags: Vec<proc_macro2::TokenStream>,
+    fields: Vec<proc_macro2::TokenStream>,
+    timestamp: Option<proc_macro2::TokenStream>,
+    columns: Vec<Column>,
+    /// `&str` expression of the measurement held by a `#[influx3_lp(table_name)]` member
+    table_name: Option<proc_macro2::TokenStream>,
+    /// expression estimating the length of the tags, fields and timestamp
+    size_hint: proc_macro2::TokenStream,
+    /// const expressions of `LpColumnCounts`
+    tag_count: proc_macro2::TokenStream,
+    field_count: proc_macro2::TokenStream,
+    /// types of the flattened members, whose counts are added
+    flattened: Vec<syn::Type>,
+    /// the `#[influx3_lp(explode)]` member, and the field entry writing its element `v`
+    explode: Option<(proc_macro2::TokenStream, proc_macro2::TokenStream)>,
+    /// `Precision` variant given to the timestamp
+    precision: Option<&'static str>,
+    /// whether tags are sorted, and whether they are gathered at runtime to be checked for
+    /// duplicates and sorted, because a flattened member or a tags map has keys unknown here
+    tag_order: (bool, bool),
+    /// whether fields are gathered at runtime to be checked for duplicates, for the same reason
+    check_fields: bool,
+}
+
+/// An entry of `lp_schema()`, a column of the type itself or the columns of a flattened member.
+enum Column {
+    /// `LpColumn` expression
+    Own(proc_macro2::TokenStream),
+    Nested(syn::Type),
+}
+
+/// Bytes reserved for each value, whose length is only known at runtime.
+const VALUE_SLACK: usize = 16;
+
+/// Turn the members of a tuple struct into named members `_0`, `_1`, ... whose key is given
+/// by `names`, unless they have a `name` of their own or no key at all, like the timestamp.
+fn name_tuple_members(members: &syn::FieldsUnnamed,
+                      names: &[syn::LitStr],
+                      struct_name: &syn::Ident)
+                      -> syn::Result<syn::Fields> {
+    if names.len() != members.unnamed.len() {
+        return Err(syn::Error::new_spanned(
+            struct_name,
+            format!("names gives {} keys for {} members", names.len(), members.unnamed.len())));
+    }
+    let mut named = syn::FieldsNamed { brace_token: Default::default(), named: Default::default() };
+    for (i, (field, key)) in members.unnamed.iter().zip(names).enumerate() {
+        let attrs = FieldAttrs::parse(field)?;
+        let mut field = field.clone();
+        field.ident = Some(syn::Ident::new(&format!("_{}", i), syn::spanned::Spanned::span(&field.ty)));
+        let keyless = attrs.is_timestamp || attrs.skip || attrs.is_fields || attrs.is_tags || attrs.flatten || attrs.is_table_name;
+        if attrs.name.is_none() && !keyless {
+            field.attrs.push(syn::parse_quote! { #[influx3_lp(name = #key)] });
+        }
+        named.named.push(field);
+    }
+    Ok(syn::Fields::Named(named))
+}
+
+/// Generate the code writing `members`, `access` gives the expression reaching a member,
+/// e.g. `self.temp`.
+fn expand_members(members: &syn::Fields,
+                  attrs: &StructAttrs,
+                  access: &dyn Fn(&syn::Ident) -> proc_macro2::TokenStream)
+                  -> syn::Result<Members> {
+    let mut fields = Vec::new();
+    let mut tags = Vec::new();
+    let mut timestamp = None;
+    let mut columns = Vec::new();
+    let mut keys = Vec::new();
+    let mut table_name = None;
+    let mut timestamp_member = None;
+    let style = FieldStyle::new(attrs);
+    let mut size_hint = 0;
+    let mut nested_hints = Vec::new();
+    let mut flattened = Vec::new();
+    let mut field_maps = 0;
+    let mut tag_maps = 0;
+    let mut explode = None;
+    let mut members_precision = None;
+
+    let rename_all = attrs.rename_all;
+    let mut coalesce = attrs.coalesce.clone();
+    let find_field = |member: &syn::Ident| {
+        members.iter()
+            .find(|f| f.ident.as_ref() == Some(member))
+            .ok_or_else(|| syn::Error::new_spanned(member, format!("no field named `{}`", member)))
+    };
+    for group in &coalesce {
+        for member in &group.members {
+            find_field(member)?;
+        }
+    }
+
+    // field level attributes
     // #[influx3_lp(tag)]
     // #[influx3_lp(timestamp)]
-    if let syn::Data::Struct(data_struct) = &input.data {
-        for field in &data_struct.fields {
-            let ident = field.ident.as_ref().unwrap();
-            let mut is_tag = false;
-            let mut is_timestamp = false;
-
-            // parse attributes
-            for attr in &field.attrs {
-                if attr.path().is_ident("influx3_lp") {
-                    let _ = attr.parse_nested_meta(|meta| {
-                        if meta.path.is_ident("timestamp") {
-                            is_timestamp = true;
-                        } else if meta.path.is_ident("tag") {
-                            is_tag = true;
-                        }
-                        Ok(())
-                    });
+    // #[influx3_lp(field, name = "...")]
+    for field in members {
+        let ident = field.ident.as_ref().unwrap();
+        let expr = access(ident);
+        let FieldAttrs {
+            is_tag, is_timestamp, skip, is_fields, is_tags, flatten, is_table_name, name, help, unit, precision,
+            timestamp_precision, max_len, len_unit, truncate, no_len_check, cast, explicit_float, duration_unit, trim, display, skip_if,
+            default, explode: is_exploded, ..
+        } = FieldAttrs::parse(field)?;
+        let limit = StrLimit { max_len, len_unit, truncate, unchecked: no_len_check };
+        // how the value of a plain field is written
+        let formatted = precision.is_some() || limit.is_set() || cast.is_some() || explicit_float || duration_unit.is_some();
+        if skip {
+            continue;
+        }
+        let plain_field = !(is_tag || is_timestamp || is_fields || is_tags || flatten || is_table_name
+            || coalesce.iter().any(|g| g.members.contains(ident)));
+        if skip_if.is_some() && !plain_field {
+            return Err(syn::Error::new_spanned(ident, "skip_if only applies to plain fields"));
+        }
+        if default.is_some() && !plain_field {
+            return Err(syn::Error::new_spanned(ident, "default only applies to plain Option fields"));
+        }
+        if is_exploded && (!plain_field || skip_if.is_some() || default.is_some()) {
+            return Err(syn::Error::new_spanned(ident, "an exploded member can't be a tag, timestamp or coalesced, nor have skip_if or default"));
+        }
+        if is_exploded && explode.is_some() {
+            return Err(syn::Error::new_spanned(ident, "only one member can be exploded"));
+        }
+
+        // the measurement is only known at runtime
+        if is_table_name {
+            if is_tag || is_timestamp || is_fields || is_tags || flatten || name.is_some() || formatted {
+                return Err(syn::Error::new_spanned(ident, "a table_name member can't be combined with other attributes"));
+            }
+            if table_name.is_some() {
+                return Err(syn::Error::new_spanned(ident, "only one member can hold the table_name"));
+            }
+            table_name = Some(quote! { ::core::convert::AsRef::<str>::as_ref(&#expr) });
+            size_hint += VALUE_SLACK;
+            continue;
+        }
+
+        // entries of a map, sorted by key as they are only known at runtime
+        if is_fields {
+            if is_tag || is_timestamp || is_tags || name.is_some() || formatted {
+                return Err(syn::Error::new_spanned(ident, "a fields map can't be combined with other attributes"));
+            }
+            // the values of the map keep the `bool_format` and `unsigned` of the struct
+            let write_value = if style.is_set() {
+                let FieldStyle { short_bool, signed } = style;
+                quote! {
+                    |v, buf| {
+                        use ::influx3_lp::__private::{StyledAny as _, StyledBool as _, StyledUnsigned as _};
+                        let style = ::influx3_lp::__private::FieldStyle { short_bool: #short_bool, signed: #signed };
+                        (&::influx3_lp::__private::Styled(v, style)).write_styled(buf, "")
+                    }
                 }
+            } else {
+                quote! { |v, buf| ::influx3_lp::LpFieldValue::write_field(v, buf, "") }
+            };
+            fields.push((RenameRule::apply(rename_all, ident), quote! {
+                use ::influx3_lp::__private::{AnyOrder as _, InsertionOrder as _};
+                let sorted = (&::influx3_lp::__private::MapOrder(&#expr)).sorted();
+                ::influx3_lp::__private::write_field_map(&#expr, buf, buf.len() == fields_start, sorted, #write_value)?;
+            }));
+            size_hint += 4 * VALUE_SLACK;
+            field_maps += 1;
+            continue;
+        }
+        if is_tags {
+            if is_tag || is_timestamp || name.is_some() || formatted || trim {
+                return Err(syn::Error::new_spanned(ident, "a tags map can't be combined with other attributes"));
             }
+            tags.push((RenameRule::apply(rename_all, ident), quote! {
+                use ::influx3_lp::__private::{AnyOrder as _, InsertionOrder as _};
+                let sorted = (&::influx3_lp::__private::MapOrder(&#expr)).sorted();
+                ::influx3_lp::__private::write_tag_map(&#expr, buf, ctx.guard, sorted)?;
+            }));
+            size_hint += 4 * VALUE_SLACK;
+            tag_maps += 1;
+            continue;
+        }
 
-            if is_tag {
-                if let Some(_) = is_option(&field.ty) {
-                    let tag_key = ident.to_string().escape_tag_key();
-                    tags.push(quote! {
-                        if let Some(v) = &self.#ident {
-                            parts.push(format!("{}={}", 
-                                               #tag_key, 
-                                               v.to_string()
-                                               .replace(",", "\\,")
-                                               .replace(" ", "\\ ")
-                                               .replace("=", "\\=")));
-                        }
-                    });                    
-                } else {
-                    let tag_key = ident.to_string().escape_tag_key();
-                    tags.push(quote! {
-                        parts.push(format!("{}={}", 
-                                           #tag_key, 
-                                           self.#ident.to_string()
-                                           .replace(",", "\\,")
-                                           .replace(" ", "\\ ")
-                                           .replace("=", "\\=")));
-                    });
+        // the member's tags go with the tags and its fields with the fields,
+        // its table name and timestamp are ignored
+        if flatten {
+            if is_tag || is_timestamp || is_fields || is_tags || name.is_some() || formatted {
+                return Err(syn::Error::new_spanned(ident, "a flattened member can't be combined with other attributes"));
+            }
+            let key = ident.to_string();
+            tags.push((key.clone(), quote! {
+                ::influx3_lp::Influx3Lp::__write_tags(&#expr, buf, ctx)?;
+            }));
+            fields.push((key, quote! {
+                ::influx3_lp::Influx3Lp::__write_fields(&#expr, buf, fields_start)?;
+            }));
+            nested_hints.push(quote! { ::influx3_lp::Influx3Lp::__size_hint(&#expr) });
+            columns.push(Column::Nested(field.ty.clone()));
+            flattened.push(field.ty.clone());
+            continue;
+        }
+
+        // all members of a coalesce group are written where the first of them is declared
+        if let Some(group) = coalesce.iter_mut().find(|g| g.members.contains(ident)) {
+            if is_tag || is_timestamp {
+                return Err(syn::Error::new_spanned(ident, "only fields can be coalesced"));
+            }
+            // the members share one way of writing the value, and the key and metadata of the first
+            if formatted || trim || display || timestamp_precision.is_some() {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "a coalesced field can't have precision, max_len, len_unit, on_overflow, no_len_check, as, float_style or duration_unit"));
+            }
+            let first = group.members[0] == *ident;
+            if !first && (name.is_some() || help.is_some() || unit.is_some()) {
+                return Err(syn::Error::new_spanned(ident, "only the first coalesced field can have a name, help or unit"));
+            }
+            if group.key.is_some() && name.is_some() {
+                return Err(syn::Error::new_spanned(ident, "the key of the coalesce group replaces the name of its fields"));
+            }
+            if group.done {
+                continue;
+            }
+            group.done = true;
+
+            let first_attrs = FieldAttrs::parse(find_field(&group.members[0])?)?;
+            let raw_key = match &group.key {
+                Some(key) => key.clone(),
+                None => first_attrs.name.unwrap_or_else(|| {
+                    let key = RenameRule::apply(rename_all, &group.members[0]);
+                    affix_field_key(key, &attrs.field_prefix, &attrs.field_suffix)
+                }),
+            };
+            check_key(&raw_key, ident)?;
+            check_unique(&raw_key, ident, &mut keys)?;
+            let field_key = escape_key(&raw_key);
+            let mut branches = Vec::new();
+            for member in &group.members {
+                let (_, depth) = unwrap_options(&find_field(member)?.ty);
+                if depth == 0 {
+                    return Err(syn::Error::new_spanned(member, "coalesced fields must be Option<T>"));
+                }
+                let pat = some_pattern(depth);
+                let member_access = access(member);
+                let entry = field_entry(&field_key, field_value(&raw_key, None, StrLimit::new(StrLimit::default(), attrs), None, false, None, style,
+                                                                proc_macro2::Span::call_site()));
+                branches.push(quote! {
+                    if let #pat = &#member_access {
+                        #entry
+                    }
+                });
+            }
+            fields.push((raw_key.clone(), quote! {
+                #(#branches)else*
+            }));
+            let help = option_tokens(first_attrs.help);
+            let unit = option_tokens(first_attrs.unit);
+            columns.push(Column::Own(quote! {
+                ::influx3_lp::LpColumn {
+                    key: #raw_key,
+                    kind: ::influx3_lp::LpColumnKind::Field,
+                    help: #help,
+                    unit: #unit,
                 }
-            } else if is_timestamp {
-                if is_option(&field.ty).is_some() {
-                    timestamp = Some(quote! {
-                        let ts = if let Some(v) = self.#ident {
-                            v.to_string()
+            }));
+            continue;
+        }
+
+        if is_timestamp && name.is_some() {
+            return Err(syn::Error::new_spanned(ident, "a timestamp has no key to rename"));
+        }
+        if is_timestamp {
+            if let Some(other) = &timestamp_member {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("only one member can be the timestamp, `{}` already is", other)));
+            }
+            timestamp_member = Some(ident.clone());
+        }
+        if (is_tag || is_timestamp) && precision.is_some() {
+            return Err(syn::Error::new_spanned(ident, "precision only applies to float fields"));
+        }
+        if !is_timestamp && timestamp_precision.is_some() {
+            return Err(syn::Error::new_spanned(ident, "a precision like \"ms\" only applies to the timestamp"));
+        }
+        if timestamp_precision.is_some() {
+            members_precision = timestamp_precision;
+        }
+        if (is_tag || is_timestamp || precision.is_some()) && limit.is_set() {
+            return Err(syn::Error::new_spanned(ident, "max_len, len_unit, on_overflow and no_len_check only apply to string fields"));
+        }
+        if no_len_check && (max_len.is_some() || len_unit.is_some() || truncate) {
+            return Err(syn::Error::new_spanned(ident, "no_len_check can't be combined with max_len, len_unit or on_overflow"));
+        }
+        if (is_tag || is_timestamp || precision.is_some() || limit.is_set()) && cast.is_some() {
+            return Err(syn::Error::new_spanned(ident, "as only applies to number fields without precision"));
+        }
+        if (is_tag || is_timestamp || precision.is_some() || limit.is_set() || cast.is_some()) && explicit_float {
+            return Err(syn::Error::new_spanned(ident, "float_style only applies to float fields without precision or as"));
+        }
+        if (is_tag || is_timestamp || precision.is_some() || limit.is_set() || cast.is_some() || explicit_float)
+            && duration_unit.is_some()
+        {
+            return Err(syn::Error::new_spanned(ident, "duration_unit only applies to Duration fields"));
+        }
+        if trim && !is_tag {
+            return Err(syn::Error::new_spanned(ident, "trim only applies to tags"));
+        }
+        if display && !is_tag {
+            return Err(syn::Error::new_spanned(ident, "display only applies to tags"));
+        }
+        let limit = StrLimit::new(limit, attrs);
+
+        // the key as it appears in line protocol, before escaping
+        let raw_key = name.unwrap_or_else(|| {
+            let key = RenameRule::apply(rename_all, ident);
+            if is_tag { key } else { affix_field_key(key, &attrs.field_prefix, &attrs.field_suffix) }
+        });
+        if !is_timestamp {
+            check_key(&raw_key, ident)?;
+            check_unique(&raw_key, ident, &mut keys)?;
+        }
+
+        // metadata only shows up in lp_schema(), never in the line itself
+        let column_key = raw_key.clone();
+        let column_kind = if is_tag {
+            quote! { ::influx3_lp::LpColumnKind::Tag }
+        } else if is_timestamp {
+            quote! { ::influx3_lp::LpColumnKind::Timestamp }
+        } else {
+            quote! { ::influx3_lp::LpColumnKind::Field }
+        };
+        let help = option_tokens(help);
+        let unit = option_tokens(unit);
+        columns.push(Column::Own(quote! {
+            ::influx3_lp::LpColumn {
+                key: #column_key,
+                kind: #column_kind,
+                help: #help,
+                unit: #unit,
+            }
+        }));
+
+        if is_tag {
+            let tag_key = escape_key(&raw_key);
+            let trim_tag = trim.then(|| quote! {
+                let value = ::influx3_lp::__private::trim_tag_value(value);
+            });
+            let push_tag = quote! {
+                #trim_tag
+                let value = match ctx.guard.check(#raw_key, &value) {
+                    ::influx3_lp::TagDecision::Keep => Some(value),
+                    ::influx3_lp::TagDecision::Drop => None,
+                    ::influx3_lp::TagDecision::Replace(v) => Some(::influx3_lp::__private::Cow::Owned(v)),
+                };
+                // InfluxDB treats an empty tag as a missing one
+                if let Some(v) = value.filter(|v| !v.is_empty()) {
+                    ::influx3_lp::__private::check_tag_value(#raw_key, &v)?;
+                    buf.push(',');
+                    buf.push_str(#tag_key);
+                    buf.push('=');
+                    ::influx3_lp::__private::escape_tag_value_into(&v, buf);
+                }
+            };
+            // a foreign type without `LpTagValue` is written with its `Display`
+            let tag_value = |v: proc_macro2::TokenStream| if display {
+                quote! { ::influx3_lp::__private::Cow::<str>::Owned(::influx3_lp::__private::format!("{}", #v)) }
+            } else {
+                quote! { ::influx3_lp::LpTagValue::tag_value(#v) }
+            };
+            let (_, depth) = unwrap_options(&field.ty);
+            if depth > 0 {
+                let pat = some_pattern(depth);
+                let value = tag_value(quote! { v });
+                tags.push((raw_key.clone(), quote! {
+                    if let #pat = &#expr {
+                        let value = #value;
+                        #push_tag
+                    }
+                }));
+            } else {
+                let value = tag_value(quote! { &#expr });
+                tags.push((raw_key.clone(), quote! {
+                    {
+                        let value = #value;
+                        #push_tag
+                    }
+                }));
+            }
+        } else if is_timestamp {
+            let precision = quote::format_ident!("{}", timestamp_precision.unwrap_or("Nanos"));
+            let (ty, depth) = unwrap_options(&field.ty);
+            // a type which can't be a timestamp is reported on the member's type
+            let write_timestamp = quote::quote_spanned! { syn::spanned::Spanned::span(ty)=>
+                ::influx3_lp::LpTimestamp::write_timestamp
+            };
+            if depth > 0 {
+                let pat = some_pattern(depth);
+                timestamp = Some(quote! {
+                    if let #pat = &#expr {
+                        #write_timestamp(v, buf, ::influx3_lp::Precision::#precision)?;
+                    }
+                });
+            } else {
+                timestamp = Some(quote! {
+                    #write_timestamp(&#expr, buf, ::influx3_lp::Precision::#precision)?;
+                });
+            }
+        } else {
+            let field_key = escape_key(&raw_key);
+            let (inner, depth) = unwrap_options(&field.ty);
+            if !is_exploded && let Some(hint) = unsupported_field_type(inner) {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    format!("`{}` can't be a field value of `{}`, {}", quote! { #inner }.to_string().replace(' ', ""), ident, hint)));
+            }
+            let entry = field_entry(&field_key, field_value(&raw_key, precision, limit, cast, explicit_float, duration_unit, style,
+                                                            syn::spanned::Spanned::span(inner)));
+            if is_exploded {
+                // written by the line itself, once per element, after the other fields
+                explode = Some((expr, entry));
+                continue;
+            }
+            let write = if let Some(default) = default {
+                if depth == 0 {
+                    return Err(syn::Error::new_spanned(ident, "default only applies to Option fields"));
+                }
+                // `None` at any depth writes the default, which must have the inner type
+                let pat = some_pattern(depth);
+                quote! {
+                    {
+                        let default: #inner;
+                        let v = if let #pat = &#expr {
+                            v
                         } else {
-                            String::new()
+                            default = #default;
+                            &default
                         };
-                    });
-                } else {
-                    timestamp = Some(quote! {
-                        let ts = self.#ident.to_string();
-                    });
+                        #entry
+                    }
+                }
+            } else if depth > 0 {
+                // `None` at any depth leaves the field out
+                let pat = some_pattern(depth);
+                quote! {
+                    if let #pat = &#expr {
+                        #entry
+                    }
                 }
             } else {
-                if let Some(ty) = is_option(&field.ty) {
-                    let field_key = ident.to_string().escape_field_key();
-                    fields.push(quote! {
-                        if let Some(v) = &self.#ident {
-                            fields.push(format!(
-                                "{}={}",
-                                #field_key,
-                                {
-                                    if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i8>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i16>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i32>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i64>()
-                                    {
-                                        format!("{}i", v)
-                                    } else if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u8>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u16>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u32>()
-                                        || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u64>() 
-                                    {
-                                        format!("{}u", v)
-                                    } else if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<String>() 
-                                    {
-                                        let t = v.to_string();
-                                        if t.len() > 64 * 1024 {
-                                            panic!("Length of string field value has a limit of 64K");
-                                        }
-                                        // string field value should be qutoed
-                                        let t = t.replace("\\", "\\\\").replace("\"", "\\\"");
-                                        format!("\"{}\"", t)
-                                    } else {
-                                        format!("{}", v)
-                                    }
-                                }
-                            ));
-                        }
-                    });
-                } else {
-                    let ty = &field.ty;
-                    let field_key = ident.to_string().escape_field_key();
-
-                    fields.push(quote! {
-                        fields.push(format!(
-                            "{}={}",
-                            #field_key,
-                            {
-                                let v = &self.#ident;
-                                if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i8>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i16>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i32>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<i64>()
-                                {
-                                    format!("{}i", v)
-                                } else if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u8>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u16>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u32>()
-                                    || std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<u64>() 
-                                {
-                                    format!("{}u", v)
-                                } else if std::any::TypeId::of::<#ty>() == std::any::TypeId::of::<String>() 
-                                {
-                                    let t = v.to_string();
-                                    if t.len() > 64 * 1024 {
-                                        panic!("Length of string field value has a limit of 64K");
-                                    }
-                                    // string field value should be qutoed
-                                    let t = t.replace("\\", "\\\\").replace("\"", "\\\"");
-                                    format!("\"{}\"", t)
-                                } else {
-                                    format!("{}", v)
-                                }
-                            }
-                        ));
-                    });
+                quote! {
+                    {
+                        let v = &#expr;
+                        #entry
+                    }
                 }
-            }
+            };
+            // the predicate sees the whole member, `Option` included
+            let write = match skip_if {
+                Some(skip_if) => quote! {
+                    if !#skip_if(&#expr) {
+                        #write
+                    }
+                },
+                None => write,
+            };
+            fields.push((raw_key.clone(), write));
         }
     }
 
-    let table_name = table_name.expect("Missing table_name in #[influx3_lp]");
-    if fields.len() == 0 {
-        panic!("{} should have at least one field", struct_name.to_string());
+    // `,key=value` for every tag and field, ` 1735545600000000000` for the timestamp
+    size_hint += keys.iter().map(|(key, _)| key.len() + 2 + VALUE_SLACK).sum::<usize>();
+    if timestamp.is_some() {
+        size_hint += 20;
     }
 
-    let expanded = if let Some(timestamp_code) = timestamp {
-        quote! {
-            impl Influx3Lp for #struct_name {
-                fn to_lp(&self) -> String {
-                    let mut parts: Vec<String> = Vec::new();
-                    let mut fields: Vec<String> = Vec::new();
+    if let Some(order) = &attrs.order {
+        sort_by_order(&mut tags, order);
+        sort_by_order(&mut fields, order);
+    }
+    // a flattened member and a map have an entry but no static count of their own
+    let tag_count = tags.len() - flattened.len() - tag_maps;
+    let field_count = fields.len() - flattened.len() - field_maps + usize::from(explode.is_some());
+    let tag_count = quote! { #tag_count #(+ <#flattened as ::influx3_lp::LpColumnCounts>::TAG_COUNT)* };
+    let field_count = quote! { #field_count #(+ <#flattened as ::influx3_lp::LpColumnCounts>::FIELD_COUNT)* };
 
-                    #(#tags)*
+    if attrs.sort_tags {
+        // keys are known here, so nothing is sorted at runtime unless some are not
+        tags.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    let tag_order = (attrs.sort_tags, !flattened.is_empty() || tag_maps > 0);
+    let check_fields = !flattened.is_empty() || field_maps > 0;
+    if attrs.sort_fields {
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    Ok(Members {
+        tags: tags.into_iter().map(|(_, code)| code).collect(),
+        fields: fields.into_iter().map(|(_, code)| code).collect(),
+        timestamp,
+        columns,
+        table_name,
+        size_hint: quote! { #size_hint #(+ #nested_hints)* },
+        tag_count,
+        field_count,
+        flattened,
+        explode,
+        precision: members_precision,
+        tag_order,
+        check_fields,
+    })
+}
 
-                    #(#fields)*
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &input.ident;
+    let attrs = StructAttrs::parse(&input.attrs)?;
 
-                    #timestamp_code
+    let mut explode = None;
+    let mut precision = None;
+    let tag_order;
+    let check_fields;
+    let (table_name, tags, fields, timestamp, columns, size_hint, name, counts) = match &input.data {
+        syn::Data::Enum(data_enum) => {
+            // one measurement per variant, every method matches on `self`
+            let mut tables = Vec::new();
+            let mut tags = Vec::new();
+            let mut fields = Vec::new();
+            let mut timestamps = Vec::new();
+            let mut columns = Vec::new();
+            let mut size_hints = Vec::new();
+            let mut names = Vec::new();
+            let mut tag_orders = Vec::new();
+            let mut field_checks = Vec::new();
+            let mut tag_counts = Vec::new();
+            let mut field_counts = Vec::new();
+            let mut flattened = Vec::new();
+            for variant in &data_enum.variants {
+                let variant_name = &variant.ident;
+                let syn::Fields::Named(named) = &variant.fields else {
+                    return Err(syn::Error::new_spanned(variant, "only variants with named fields are supported"));
+                };
+                let own_attrs = StructAttrs::parse(&variant.attrs)?;
+                let own_table_name = own_attrs.table_name.clone();
+                let variant_attrs = own_attrs.inherit(&attrs);
 
-                    let tags_str = if parts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(",{}", parts.join(","))
-                    };
+                // members are bound to prefixed names so they can't shadow generated variables
+                let bind = |ident: &syn::Ident| quote::format_ident!("__{}", ident);
+                let members = expand_members(&variant.fields, &variant_attrs, &|ident| {
+                    let local = bind(ident);
+                    quote! { (*#local) }
+                })?;
+                if members.fields.is_empty() {
+                    return Err(syn::Error::new_spanned(variant, format!("{} should have at least one field", variant_name)));
+                }
+                let idents = named.named.iter().map(|f| f.ident.as_ref().unwrap());
+                let locals = idents.clone().map(bind);
+                let pattern = quote! { Self::#variant_name { #(#idents: #locals,)* .. } };
 
-                    if ts.len() > 0 {
-                        format!(
-                            "{}{} {} {}",
-                            #table_name,
-                            tags_str,
-                            fields.join(","),
-                            ts
-                        )
-                    } else {
-                        format!(
-                            "{}{} {}",
-                            #table_name,
-                            tags_str,
-                            fields.join(","),
-                        )
+                let Members {
+                    tags: variant_tags,
+                    fields: variant_fields,
+                    timestamp,
+                    columns: variant_columns,
+                    table_name,
+                    size_hint,
+                    tag_count,
+                    field_count,
+                    flattened: variant_flattened,
+                    explode: variant_explode,
+                    precision: variant_precision,
+                    tag_order: (sort_tags, gather_tags),
+                    check_fields,
+                } = members;
+                if let Some(variant_precision) = variant_precision {
+                    if precision.is_some_and(|p| p != variant_precision) {
+                        return Err(syn::Error::new_spanned(variant, "every variant must use the same timestamp precision"));
                     }
+                    precision = Some(variant_precision);
+                }
+                if variant_explode.is_some() {
+                    return Err(syn::Error::new_spanned(variant, "explode only applies to structs"));
+                }
+                // a member holding the table name wins over the one of the enum
+                let literal = if table_name.is_some() { own_table_name } else { variant_attrs.table_name };
+                let table_len = literal.as_ref().map_or(0, TableName::len_hint);
+                size_hints.push(quote! { #pattern => #table_len + #size_hint, });
+                let (table_name, name) = measurement(literal, table_name, variant)?;
+                tables.push(quote! { #pattern => { #table_name } });
+                names.push(quote! { #pattern => #name, });
+                tag_orders.push(quote! { #pattern => (#sort_tags, #gather_tags), });
+                field_checks.push(quote! { #pattern => #check_fields, });
+                tags.push(quote! { #pattern => { #(#variant_tags)* } });
+                fields.push(quote! { #pattern => { #(#variant_fields)* } });
+                timestamps.push(quote! { #pattern => { #timestamp } });
+                columns.extend(variant_columns);
+                tag_counts.push(tag_count);
+                field_counts.push(field_count);
+                flattened.extend(variant_flattened);
+            }
+            // the largest variant
+            let tag_count = quote! { ::influx3_lp::__private::max_count(&[#(#tag_counts),*]) };
+            let field_count = quote! { ::influx3_lp::__private::max_count(&[#(#field_counts),*]) };
+            tag_order = quote! { match self { #(#tag_orders)* } };
+            check_fields = quote! { match self { #(#field_checks)* } };
+            (
+                quote! { match self { #(#tables)* } },
+                vec![quote! { match self { #(#tags)* } }],
+                vec![quote! { match self { #(#fields)* } }],
+                Some(quote! { match self { #(#timestamps)* } }),
+                columns,
+                quote! { match self { #(#size_hints)* } },
+                quote! { match self { #(#names)* } },
+                (tag_count, field_count, flattened),
+            )
+        }
+        syn::Data::Struct(data_struct) => {
+            let members = match (&data_struct.fields, &attrs.names) {
+                (syn::Fields::Unnamed(unnamed), Some(names)) => {
+                    // members are named `_0`, `_1`, ... and reached by their index
+                    let named = name_tuple_members(unnamed, names, struct_name)?;
+                    expand_members(&named, &attrs, &|ident| {
+                        let index = syn::Index::from(ident.to_string()[1..].parse::<usize>().unwrap());
+                        quote! { self.#index }
+                    })?
                 }
+                (syn::Fields::Unnamed(_), None) => {
+                    return Err(syn::Error::new_spanned(
+                        struct_name,
+                        "a tuple struct needs #[influx3_lp(names(\"x\", \"y\"))] giving the key of each member"));
+                }
+                (_, Some(names)) => {
+                    return Err(syn::Error::new_spanned(&names[0], "names only applies to tuple structs"));
+                }
+                (fields, None) => expand_members(fields, &attrs, &|ident| quote! { self.#ident })?,
+            };
+            let (table_name, name) = measurement(attrs.table_name.clone(), members.table_name, struct_name)?;
+            if members.fields.is_empty() && members.explode.is_none() {
+                return Err(syn::Error::new_spanned(struct_name, format!("{} should have at least one field", struct_name)));
             }
+            let table_len = attrs.table_name.as_ref().map_or(0, TableName::len_hint);
+            let size_hint = members.size_hint;
+            explode = members.explode;
+            precision = members.precision;
+            let (sort_tags, gather_tags) = members.tag_order;
+            tag_order = quote! { (#sort_tags, #gather_tags) };
+            let members_check_fields = members.check_fields;
+            check_fields = quote! { #members_check_fields };
+            (
+                table_name,
+                members.tags,
+                members.fields,
+                members.timestamp,
+                members.columns,
+                quote! { #table_len + #size_hint },
+                name,
+                (members.tag_count, members.field_count, members.flattened),
+            )
+        }
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(struct_name, "Influx3Lp can't be derived for unions"));
+        }
+    };
+
+    let exploded_entry = explode.as_ref().map(|(_, entry)| entry);
+    // shared by every output target, so escaping can't differ between them
+    let line = quote! {
+        #table_name
+        // extra tags, and the keys of flattened members and maps, are only known here, so the
+        // tags are gathered first to be checked for duplicates and sorted
+        let (sort_tags, gather_tags): (bool, bool) = #tag_order;
+        if ctx.extra_tags.is_empty() && !gather_tags {
+            ::influx3_lp::Influx3Lp::__write_tags(self, buf, ctx)?;
+        } else {
+            let mut tags = ::influx3_lp::__private::String::new();
+            ::influx3_lp::Influx3Lp::__write_tags(self, &mut tags, ctx)?;
+            ::influx3_lp::__private::write_merged_tags(&tags, ctx.extra_tags, sort_tags, buf)?;
         }
+
+        let fields_start = buf.len();
+        let check_fields: bool = #check_fields;
+        if check_fields {
+            let mut fields = ::influx3_lp::__private::String::new();
+            ::influx3_lp::Influx3Lp::__write_fields(self, &mut fields, 0)?;
+            ::influx3_lp::__private::write_unique_fields(&fields, buf)?;
+        } else {
+            ::influx3_lp::Influx3Lp::__write_fields(self, buf, fields_start)?;
+        }
+        #exploded_entry
+        // every field was an empty Option
+        if buf.len() == fields_start {
+            return Err(::influx3_lp::LpError::EmptyFieldSet);
+        }
+
+        // a timestamp given at the call site wins over the member
+        if let Some(ts) = ctx.timestamp {
+            buf.push(' ');
+            let _ = write!(buf, "{}", ts);
+        } else {
+            #timestamp
+        }
+    };
+    let body = match &explode {
+        // one line per element, sharing the tags, the other fields and the timestamp
+        Some((collection, _)) => quote! {
+            let mut first = true;
+            for v in &#collection {
+                if !first {
+                    buf.push('\n');
+                }
+                first = false;
+                #line
+            }
+            if first {
+                return Err(::influx3_lp::LpError::EmptyFieldSet);
+            }
+            Ok(())
+        },
+        None => quote! {
+            #line
+            Ok(())
+        },
+    };
+
+    let precision = quote::format_ident!("{}", precision.unwrap_or("Nanos"));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // a generic flattened member only has counts if its type argument has them
+    let (tag_count, field_count, flattened) = counts;
+    let mut counts_generics = input.generics.clone();
+    if !flattened.is_empty() {
+        let predicates = &mut counts_generics.make_where_clause().predicates;
+        for ty in &flattened {
+            predicates.push(syn::parse_quote! { #ty: ::influx3_lp::LpColumnCounts });
+        }
+    }
+    let counts_where = &counts_generics.where_clause;
+
+    // a static schema can't depend on a type parameter, so a generic flattened member is left out
+    let mut own_columns = Vec::new();
+    let mut parts = Vec::new();
+    let mut nested_schema = false;
+    for column in &columns {
+        match column {
+            Column::Own(column) => {
+                own_columns.push(column);
+                parts.push((quote! { &[#column] }, false));
+            }
+            Column::Nested(ty) if !uses_generics(ty, &input.generics) => {
+                nested_schema = true;
+                parts.push((quote! { <#ty as ::influx3_lp::LpColumnCounts>::__SCHEMA }, true));
+            }
+            Column::Nested(_) => {}
+        }
+    }
+    let (schema_const, schema) = if nested_schema {
+        let lens = parts.iter().map(|(part, nested)| match nested {
+            true => quote! { ::influx3_lp::__private::nested_len(#part) },
+            false => quote! { 1 },
+        });
+        let parts = parts.iter().map(|(part, nested)| quote! { (#part, #nested) });
+        let concat = quote! {
+            &::influx3_lp::__private::concat_columns::<{ 0 #(+ #lens)* }>(&[#(#parts),*])
+        };
+        // an item of its own, `Self` may only implement LpColumnCounts with more bounds
+        (
+            quote! { const __SCHEMA: &'static [::influx3_lp::LpColumn] = #concat; },
+            quote! {
+                const SCHEMA: &[::influx3_lp::LpColumn] = #concat;
+                SCHEMA
+            },
+        )
     } else {
-        quote! {
-            impl Influx3Lp for #struct_name {
-                fn to_lp(&self) -> String {
-                    let mut parts: Vec<String> = Vec::new();
-                    let mut fields: Vec<String> = Vec::new();
+        (
+            quote! { const __SCHEMA: &'static [::influx3_lp::LpColumn] = &[#(#own_columns),*]; },
+            quote! { &[#(#own_columns),*] },
+        )
+    };
 
-                    #(#tags)*
+    let expanded = quote! {
+        impl #impl_generics ::influx3_lp::LpColumnCounts for #struct_name #ty_generics #counts_where {
+            const TAG_COUNT: usize = #tag_count;
+            const FIELD_COUNT: usize = #field_count;
+            #schema_const
+        }
 
-                    #(#fields)*
+        impl #impl_generics ::influx3_lp::Influx3Lp for #struct_name #ty_generics #where_clause {
+            fn lp_schema() -> &'static [::influx3_lp::LpColumn] {
+                #schema
+            }
 
-                    let tags_str = if parts.is_empty() {
-                        String::new()
-                    } else {
-                        format!(",{}", parts.join(","))
-                    };
+            fn lp_precision() -> ::influx3_lp::Precision {
+                ::influx3_lp::Precision::#precision
+            }
+
+            #[allow(unused_variables)]
+            fn measurement(&self) -> &str {
+                #name
+            }
+
+            #[allow(unused_variables)]
+            fn __size_hint(&self) -> usize {
+                #size_hint
+            }
+
+            // generic parameters are prefixed so they can't clash with those of the struct
+            #[allow(unused_variables)]
+            fn __write_tags<__B: ::influx3_lp::__private::LpSink + ?Sized>(&self,
+                                                                           buf: &mut __B,
+                                                                           ctx: &::influx3_lp::__private::Context<'_>)
+                                                                           -> Result<(), ::influx3_lp::LpError> {
+                use ::influx3_lp::__private::LpSink as _;
+
+                #(#tags)*
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn __write_fields<__B: ::influx3_lp::__private::LpSink + ?Sized>(&self,
+                                                                             buf: &mut __B,
+                                                                             fields_start: usize)
+                                                                             -> Result<(), ::influx3_lp::LpError> {
+                use ::influx3_lp::__private::LpSink as _;
+
+                #(#fields)*
+                Ok(())
+            }
+
+            #[allow(unused_variables)]
+            fn __write_line<__B: ::influx3_lp::__private::LpSink + ?Sized>(&self,
+                                                                           buf: &mut __B,
+                                                                           ctx: &::influx3_lp::__private::Context<'_>)
+                                                                           -> Result<(), ::influx3_lp::LpError> {
+                use ::core::fmt::Write as _;
+                use ::influx3_lp::__private::LpSink as _;
 
-                    format!(
-                        "{}{} {}",
-                        #table_name,
-                        tags_str,
-                        fields.join(","),
-                    )
+                #body
+            }
+
+            fn __write_lp(&self,
+                          buf: &mut ::influx3_lp::__private::String,
+                          ctx: &::influx3_lp::__private::Context<'_>)
+                          -> Result<(), ::influx3_lp::LpError> {
+                let start = buf.len();
+                let result = ::influx3_lp::Influx3Lp::__write_line(self, buf, ctx);
+                if result.is_err() {
+                    // leave the buffer as it was before this point
+                    buf.truncate(start);
                 }
+                result
             }
         }
     };
 
+    Ok(expanded)
+}
+
+fn expand_from_lp(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &input.ident;
+    let StructAttrs {
+        table_name, coalesce, rename_all, field_prefix, field_suffix, unsigned, ..
+    } = StructAttrs::parse(&input.attrs)?;
+    // unsigned fields written as `i` integers are read back from them too
+    let signed = unsigned == Some(Unsigned::Forbid);
+    let syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(members), .. }) = &input.data else {
+        return Err(syn::Error::new_spanned(struct_name, "FromLp can only be derived for structs with named fields"));
+    };
+
+    let mut locals = Vec::new();
+    let mut tag_arms = Vec::new();
+    let mut field_arms = Vec::new();
+    let mut timestamp = None;
+    let mut inits = Vec::new();
+    let mut field_map = None;
+    let mut tag_map = None;
+    let mut table_member = None;
+    for field in &members.named {
+        let ident = field.ident.as_ref().unwrap();
+        let FieldAttrs {
+            is_tag, is_timestamp, skip, is_fields, is_tags, flatten, is_table_name, name, cast, duration_unit, default, explode, ..
+        } = FieldAttrs::parse(field)?;
+        // what these write can't be told apart from another value when reading the line
+        let unreadable = [(cast.is_some(), "as"), (duration_unit.is_some(), "duration_unit"), (default.is_some(), "default"), (explode, "explode")];
+        if let Some((_, attr)) = unreadable.iter().find(|(set, _)| *set) {
+            return Err(syn::Error::new_spanned(ident, format!("members with `{}` can't be read back by FromLp", attr)));
+        }
+        if skip {
+            inits.push(quote! { #ident: ::core::default::Default::default() });
+            continue;
+        }
+        if is_table_name {
+            inits.push(quote! { #ident: ::core::convert::From::from(parts.measurement.clone()) });
+            table_member = Some(ident);
+            continue;
+        }
+        if flatten {
+            return Err(syn::Error::new_spanned(ident, "flattened members can't be read back by FromLp"));
+        }
+        // prefixed so members can't shadow the variables of the generated code
+        let local = quote::format_ident!("__{}", ident);
+        // fields without a member of their own end up in the map
+        if is_fields {
+            if field_map.is_some() {
+                return Err(syn::Error::new_spanned(ident, "only one fields map can be read back"));
+            }
+            let field_ty = &field.ty;
+            locals.push(quote! { let mut #local = <#field_ty as ::core::default::Default>::default(); });
+            inits.push(quote! { #ident: #local });
+            field_map = Some(local);
+            continue;
+        }
+        // and so do tags
+        if is_tags {
+            if tag_map.is_some() {
+                return Err(syn::Error::new_spanned(ident, "only one tags map can be read back"));
+            }
+            let field_ty = &field.ty;
+            locals.push(quote! { let mut #local = <#field_ty as ::core::default::Default>::default(); });
+            inits.push(quote! { #ident: #local });
+            tag_map = Some(local);
+            continue;
+        }
+
+        // a coalesced field is read back into the first member of its group
+        let group = coalesce.iter().find(|g| g.members.contains(ident));
+        let key = group
+            .and_then(|g| g.key.clone())
+            .or(name)
+            .unwrap_or_else(|| {
+                let key = RenameRule::apply(rename_all, ident);
+                if is_tag || is_timestamp { key } else { affix_field_key(key, &field_prefix, &field_suffix) }
+            });
+
+        let (ty, depth) = unwrap_options(&field.ty);
+        locals.push(quote! { let mut #local: Option<#ty> = None; });
+        if depth > 0 {
+            // a missing key reads back as the outermost `None`
+            let wraps = (1..depth).map(|_| quote! { .map(Some) });
+            inits.push(quote! { #ident: #local #(#wraps)* });
+        } else {
+            let missing = if is_timestamp {
+                String::from("missing timestamp")
+            } else {
+                format!("missing {} `{}`", if is_tag { "tag" } else { "field" }, key)
+            };
+            inits.push(quote! {
+                #ident: #local.ok_or_else(|| ::influx3_lp::__private::parse_error(::influx3_lp::__private::String::from(#missing)))?
+            });
+        }
+        if group.is_some_and(|g| g.members[0] != *ident) {
+            continue;
+        }
+
+        if is_tag {
+            tag_arms.push(quote! {
+                #key => #local = Some(::influx3_lp::__private::parse_tag(&key, &value)?),
+            });
+        } else if is_timestamp {
+            if timestamp.is_some() {
+                return Err(syn::Error::new_spanned(ident, "only one member can be the timestamp"));
+            }
+            timestamp = Some(quote! {
+                if let Some(ts) = parts.timestamp {
+                    #local = Some(<#ty as ::core::convert::TryFrom<i64>>::try_from(ts).map_err(|_| {
+                        ::influx3_lp::__private::parse_error(::influx3_lp::__private::format!("timestamp `{}` is out of range", ts))
+                    })?);
+                }
+            });
+        } else {
+            field_arms.push(quote! {
+                #key => {
+                    #local = Some(::influx3_lp::__private::read_field_value::<#ty>(&value, #signed).ok_or_else(|| {
+                        ::influx3_lp::__private::parse_error(::influx3_lp::__private::format!("field `{}` has the wrong type", key))
+                    })?);
+                }
+            });
+        }
+    }
+
+    let unknown_tag = match tag_map {
+        Some(local) => quote! { ::influx3_lp::__private::read_tag_map(&mut #local, key, &value)? },
+        None => quote! {
+            return Err(::influx3_lp::__private::parse_error(::influx3_lp::__private::format!("unknown tag `{}`", key)))
+        },
+    };
+    let unknown_field = match field_map {
+        Some(local) => quote! { ::influx3_lp::__private::read_field_map(&mut #local, key, &value, #signed)? },
+        None => quote! {
+            return Err(::influx3_lp::__private::parse_error(::influx3_lp::__private::format!("unknown field `{}`", key)))
+        },
+    };
+
+    // a member holding the table name accepts any measurement
+    let check_measurement = match (table_name, table_member) {
+        (Some(table_name), None) => quote! {
+            if parts.measurement != #table_name {
+                return Err(::influx3_lp::__private::parse_error(
+                    ::influx3_lp::__private::format!("measurement `{}` doesn't match `{}`", parts.measurement, #table_name)));
+            }
+        },
+        (None, Some(_)) => quote! {},
+        (literal, member) => return measurement(literal, member.map(|_| quote! {}), struct_name).map(|(code, _)| code),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::influx3_lp::FromLp for #struct_name #ty_generics #where_clause {
+            fn from_lp(line: &str) -> Result<Self, ::influx3_lp::LpError> {
+                let parts = ::influx3_lp::parse_line(line).map_err(::influx3_lp::LpError::Parse)?;
+                #check_measurement
+
+                #(#locals)*
+                for (key, value) in parts.tags {
+                    match key.as_str() {
+                        #(#tag_arms)*
+                        _ => #unknown_tag,
+                    }
+                }
+                for (key, value) in parts.fields {
+                    match key.as_str() {
+                        #(#field_arms)*
+                        _ => #unknown_field,
+                    }
+                }
+                #timestamp
+
+                Ok(Self {
+                    #(#inits),*
+                })
+            }
+        }
+    })
+}
+
+/// Generate the code writing the measurement and a `&str` expression of it, from either the
+/// `literal` table name or the one held by a member, exactly one of them must be given.
+fn measurement(literal: Option<TableName>,
+               member: Option<proc_macro2::TokenStream>,
+               span: impl quote::ToTokens)
+               -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    match (literal, member) {
+        (Some(TableName::Literal(name)), None) => {
+            let escaped = escape_measurement(&name);
+            Ok((quote! { buf.push_str(#escaped); }, quote! { #name }))
+        }
+        // the const is only known after type checking, so it's checked by a const item
+        (Some(TableName::Const(path)), None) => Ok((
+            quote! {
+                const _: () = ::influx3_lp::__private::assert_measurement(#path);
+                ::influx3_lp::__private::escape_measurement_into(#path, buf);
+            },
+            quote! { #path },
+        )),
+        (None, Some(name)) => Ok((
+            quote! {
+                let name: &str = #name;
+                ::influx3_lp::__private::check_measurement(name)?;
+                ::influx3_lp::__private::escape_measurement_into(name, buf);
+            },
+            name,
+        )),
+        (Some(_), Some(_)) => Err(syn::Error::new_spanned(
+            span,
+            "table_name is given both as a literal and by a member, keep only one")),
+        (None, None) => Err(syn::Error::new_spanned(
+            span,
+            "Missing table_name in #[influx3_lp], set it on the struct or mark a member with #[influx3_lp(table_name)]")),
+    }
+}
 
-    TokenStream::from(expanded)
+/// Generate the code appending `key=value` to the field set, `value` is the code
+/// generated by `field_value`.
+fn field_entry(field_key: &str, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote! {
+        buf.push(if buf.len() == fields_start { ' ' } else { ',' });
+        buf.push_str(#field_key);
+        buf.push('=');
+        #value
+    }
 }
 
-/// a helper to detect if a field of struct is Option
-fn is_option(ty: &syn::Type) -> Option<&syn::Type> {
-    if let syn::Type::Path(typepath) = ty {
-        if typepath.qself.is_none() && typepath.path.segments.len() == 1 {
-            let segment = &typepath.path.segments[0];
-            if segment.ident == "Option" {
-                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                    if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
-                        return Some(inner_ty);
+/// Generate the code appending a field value bound to `v` to `buf`,
+/// how it's written is decided by the `LpFieldValue` implementation of its type,
+/// unless a float `precision`, a string `limit` or a number `cast` is given. A type without
+/// `LpFieldValue` is reported at `ty_span`.
+#[allow(clippy::too_many_arguments)]
+fn field_value(field_name: &str,
+               precision: Option<usize>,
+               limit: StrLimit,
+               cast: Option<Cast>,
+               explicit_float: bool,
+               duration_unit: Option<u128>,
+               style: FieldStyle,
+               ty_span: proc_macro2::Span)
+               -> proc_macro2::TokenStream {
+    if let Some(cast) = cast {
+        let method = match cast {
+            Cast::Float => quote! { write_as_float },
+            Cast::Integer => quote! { write_as_integer },
+        };
+        return quote! {
+            ::influx3_lp::__private::LpNumber::#method(v, buf, #field_name)?;
+        };
+    }
+    if let Some(unit) = duration_unit {
+        return quote! {
+            ::influx3_lp::__private::write_duration(v, buf, #field_name, #unit)?;
+        };
+    }
+    if explicit_float {
+        return quote! {
+            ::influx3_lp::__private::write_float_explicit(v, buf, #field_name)?;
+        };
+    }
+    match (precision, limit.is_set()) {
+        (Some(precision), _) => quote! {
+            ::influx3_lp::__private::write_float_with_precision(v, buf, #field_name, #precision)?;
+        },
+        (None, true) => {
+            // no string is longer than usize::MAX bytes, so the check is compiled out
+            let max_len = match limit.max_len {
+                Some(max_len) => quote! { #max_len },
+                None if limit.unchecked => quote! { usize::MAX },
+                None => quote! { ::influx3_lp::__private::MAX_STRING_LEN },
+            };
+            let chars = limit.len_unit == Some(LenUnit::Chars);
+            let truncate = limit.truncate;
+            let limit = quote! {
+                ::influx3_lp::__private::StrLimit { max_len: #max_len, chars: #chars, truncate: #truncate }
+            };
+            if !style.is_set() {
+                return quote! {
+                    {
+                        use ::influx3_lp::__private::{LimitedAny as _, LimitedStr as _};
+                        (&::influx3_lp::__private::MaxLen(v, #limit)).write_limited(buf, #field_name)?;
+                    }
+                };
+            }
+            // a struct level limit reaches every field, bools and unsigned keep their style
+            let FieldStyle { short_bool, signed } = style;
+            quote! {
+                {
+                    use ::influx3_lp::__private::{LimitedAny as _, LimitedStr as _};
+                    use ::influx3_lp::__private::{StyledAny as _, StyledBool as _, StyledUnsigned as _};
+                    let limited = ::influx3_lp::__private::MaxLen(v, #limit);
+                    if (&limited).is_limited() {
+                        (&limited).write_limited(buf, #field_name)?;
+                    } else {
+                        let style = ::influx3_lp::__private::FieldStyle { short_bool: #short_bool, signed: #signed };
+                        (&::influx3_lp::__private::Styled(v, style)).write_styled(buf, #field_name)?;
                     }
                 }
             }
         }
+        (None, false) if style.is_set() => {
+            let FieldStyle { short_bool, signed } = style;
+            quote! {
+                {
+                    use ::influx3_lp::__private::{StyledAny as _, StyledBool as _, StyledUnsigned as _};
+                    let style = ::influx3_lp::__private::FieldStyle { short_bool: #short_bool, signed: #signed };
+                    (&::influx3_lp::__private::Styled(v, style)).write_styled(buf, #field_name)?;
+                }
+            }
+        }
+        (None, false) => {
+            let write_field = quote::quote_spanned! { ty_span=> ::influx3_lp::LpFieldValue::write_field };
+            quote! {
+                #write_field(v, buf, #field_name)?;
+            }
+        }
     }
-    None
 }
 
-// Escape string according to line protocol
-// https://docs.influxdata.com/influxdb3/core/reference/line-protocol/#special-characters
-#[allow(dead_code)]
-trait StringExtensions {
-    fn escape_table(self) -> String;
-    fn escape_tag_key(self) -> String;
-    fn escape_tag_value(self) -> String;
-    fn escape_field_key(self) -> String;
-    fn escape_field_value(self) -> String;
+/// Reorder `(key, code)` pairs to follow `order`, keys missing from `order` keep
+/// their declaration order after the listed ones.
+fn sort_by_order(items: &mut [(String, proc_macro2::TokenStream)], order: &[String]) {
+    items.sort_by_key(|(key, _)| order.iter().position(|k| k == key).unwrap_or(usize::MAX));
 }
 
-impl StringExtensions for String {
-    fn escape_table(self) -> String {
-        self.replace(",", "\\,").replace(" ", "\\ ")
+/// Turn an optional string into `Some("...")` / `None` tokens.
+fn option_tokens(value: Option<String>) -> proc_macro2::TokenStream {
+    match value {
+        Some(v) => quote! { Some(#v) },
+        None => quote! { None },
     }
+}
 
-    fn escape_tag_key(self) -> String {
-        self.replace(",", "\\,").replace(" ", "\\ ").replace("=", "\\=")
+/// Strip every `Option` layer of `ty`, returning the inner type and the number of layers.
+fn unwrap_options(mut ty: &syn::Type) -> (&syn::Type, usize) {
+    let mut depth = 0;
+    while let Some(inner) = is_option(ty) {
+        ty = inner;
+        depth += 1;
     }
+    (ty, depth)
+}
+
+/// The pattern binding `v` inside `depth` layers of `Option`, e.g. `Some(Some(v))`.
+fn some_pattern(depth: usize) -> proc_macro2::TokenStream {
+    (0..depth).fold(quote! { v }, |pat, _| quote! { Some(#pat) })
+}
 
-    fn escape_tag_value(self) -> String {
-        self.replace(",", "\\,").replace(" ", "\\ ").replace("=", "\\=")
+/// Why a type obviously can't be written as a field value, so the error names the member
+/// rather than a missing `LpFieldValue` impl deep in the expansion. Other types are left to
+/// the trait bound.
+fn unsupported_field_type(ty: &syn::Type) -> Option<&'static str> {
+    const LIST: &str = "use #[influx3_lp(explode)] to write a line per element, or #[influx3_lp(skip)]";
+    const MAP: &str = "use #[influx3_lp(fields)] or #[influx3_lp(tags)] to write its entries";
+    match ty {
+        syn::Type::Array(_) | syn::Type::Slice(_) => Some(LIST),
+        syn::Type::Tuple(tuple) if !tuple.elems.is_empty() => {
+            Some("split it into members, or use #[influx3_lp(skip)]")
+        }
+        syn::Type::Reference(reference) => unsupported_field_type(&reference.elem),
+        syn::Type::Path(typepath) if typepath.qself.is_none() => {
+            let segment = typepath.path.segments.last()?;
+            match segment.ident.to_string().as_str() {
+                "Vec" | "VecDeque" | "LinkedList" | "HashSet" | "BTreeSet" | "IndexSet" => Some(LIST),
+                "HashMap" | "BTreeMap" | "IndexMap" => Some(MAP),
+                _ => None,
+            }
+        }
+        _ => None,
     }
+}
 
-    fn escape_field_key(self) -> String {
-        self.replace(",", "\\,").replace(" ", "\\ ").replace("=", "\\=")
+/// Whether `ty` names a type, lifetime or const parameter of `generics`.
+fn uses_generics(ty: &syn::Type, generics: &syn::Generics) -> bool {
+    fn mentions(tokens: proc_macro2::TokenStream, params: &[&syn::Ident]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(ident) => params.contains(&&ident),
+            proc_macro2::TokenTree::Group(group) => mentions(group.stream(), params),
+            _ => false,
+        })
     }
+    let params: Vec<&syn::Ident> = generics.params.iter().map(|param| match param {
+        syn::GenericParam::Type(param) => &param.ident,
+        syn::GenericParam::Lifetime(param) => &param.lifetime.ident,
+        syn::GenericParam::Const(param) => &param.ident,
+    }).collect();
+    mentions(quote! { #ty }, &params)
+}
 
-    fn escape_field_value(self) -> String {
-        self.replace("\\", "\\\\").replace("\"", "\\\"")
+/// a helper to detect if a field of struct is Option
+fn is_option(ty: &syn::Type) -> Option<&syn::Type> {
+    if let syn::Type::Path(typepath) = ty
+        && typepath.qself.is_none()
+        && typepath.path.segments.len() == 1
+    {
+        let segment = &typepath.path.segments[0];
+        if segment.ident == "Option"
+            && let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+            && let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first()
+        {
+            return Some(inner_ty);
+        }
     }
+    None
+}
+
+// Escape keys known at compile time according to line protocol
+// https://docs.influxdata.com/influxdb3/core/reference/line-protocol/#special-characters
+//
+// A proc-macro crate can't export functions, so these special chars mirror the ones of
+// `influx3_lp/src/escape.rs`, which escapes keys and values known only at runtime.
+const MEASUREMENT_SPECIAL: &[char] = &['\\', ',', ' '];
+const KEY_SPECIAL: &[char] = &['\\', ',', ' ', '='];
+
+fn escape(s: &str, special: &[char]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn escape_measurement(s: &str) -> String {
+    escape(s, MEASUREMENT_SPECIAL)
+}
+
+/// Tag keys and field keys are escaped alike.
+fn escape_key(s: &str) -> String {
+    escape(s, KEY_SPECIAL)
 }
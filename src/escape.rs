@@ -0,0 +1,107 @@
+//! Escaping of keys and values according to
+//! [line protocol](https://docs.influxdata.com/influxdb3/core/reference/line-protocol/#special-characters).
+//!
+//! Keys known at compile time are escaped by the macro with the same special chars, these are for keys and values
+//! only known at runtime, and for lines built by hand. The `_into` variants write straight to the output in a single
+//! pass, without an intermediate `String`.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::fmt::Write;
+
+// all special chars are ASCII, so their byte positions are char boundaries
+const MEASUREMENT_SPECIAL: &[u8] = b"\\, ";
+const TAG_SPECIAL: &[u8] = b"\\, =";
+const FIELD_STRING_SPECIAL: &[u8] = b"\\\"";
+
+/// Append `s` to `w` with a backslash before every byte of `special`.
+fn escape_into<W: Write + ?Sized>(s: &str, special: &[u8], w: &mut W) {
+    // a non-ASCII byte could be inside a multibyte char, slicing there would panic
+    debug_assert!(special.is_ascii());
+    // most values have nothing to escape and are written in one go
+    let Some(first) = s.bytes().position(|b| special.contains(&b)) else {
+        let _ = w.write_str(s);
+        return;
+    };
+
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate().skip(first) {
+        if special.contains(&b) {
+            let _ = w.write_str(&s[start..i]);
+            let _ = w.write_char('\\');
+            // the special char itself starts the next run
+            start = i;
+        }
+    }
+    let _ = w.write_str(&s[start..]);
+}
+
+/// Escape `s`, borrowing it when there is nothing to escape.
+fn escape<'a>(s: &'a str, special: &[u8]) -> Cow<'a, str> {
+    if !s.bytes().any(|b| special.contains(&b)) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() + 4);
+    escape_into(s, special, &mut out);
+    Cow::Owned(out)
+}
+
+/// Escape a measurement, backslashes, commas and spaces.
+///
+/// ```rust
+/// use influx3_lp::escape_measurement;
+///
+/// assert_eq!(escape_measurement("home"), "home");
+/// assert_eq!(escape_measurement("my home,1"), "my\\ home\\,1");
+/// ```
+pub fn escape_measurement(s: &str) -> Cow<'_, str> {
+    escape(s, MEASUREMENT_SPECIAL)
+}
+
+/// Escape a tag key, backslashes, commas, equals signs and spaces.
+pub fn escape_tag_key(s: &str) -> Cow<'_, str> {
+    escape(s, TAG_SPECIAL)
+}
+
+/// Escape a tag value, backslashes, commas, equals signs and spaces.
+pub fn escape_tag_value(s: &str) -> Cow<'_, str> {
+    escape(s, TAG_SPECIAL)
+}
+
+/// Escape a field key, backslashes, commas, equals signs and spaces.
+pub fn escape_field_key(s: &str) -> Cow<'_, str> {
+    escape(s, TAG_SPECIAL)
+}
+
+/// Escape the content of a string field value, backslashes and double quotes. The surrounding
+/// quotes are not added.
+///
+/// ```rust
+/// use influx3_lp::escape_field_value;
+///
+/// assert_eq!(format!("\"{}\"", escape_field_value(r#"say "hi""#)), r#""say \"hi\"""#);
+/// ```
+pub fn escape_field_value(s: &str) -> Cow<'_, str> {
+    escape(s, FIELD_STRING_SPECIAL)
+}
+
+pub fn escape_measurement_into<W: Write + ?Sized>(s: &str, w: &mut W) {
+    escape_into(s, MEASUREMENT_SPECIAL, w)
+}
+
+pub fn escape_tag_key_into<W: Write + ?Sized>(s: &str, w: &mut W) {
+    escape_into(s, TAG_SPECIAL, w)
+}
+
+pub fn escape_tag_value_into<W: Write + ?Sized>(s: &str, w: &mut W) {
+    escape_into(s, TAG_SPECIAL, w)
+}
+
+pub fn escape_field_key_into<W: Write + ?Sized>(s: &str, w: &mut W) {
+    escape_into(s, TAG_SPECIAL, w)
+}
+
+/// Escape the content of a string field value, without the surrounding quotes.
+pub fn escape_field_string_into<W: Write + ?Sized>(s: &str, w: &mut W) {
+    escape_into(s, FIELD_STRING_SPECIAL, w)
+}
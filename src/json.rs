@@ -0,0 +1,68 @@
+//! Fields whose values are only known as `serde_json::Value`, e.g. when bridging JSON telemetry.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde_json::{Map, Value};
+
+use crate::{LineBuilder, LpError};
+
+impl LineBuilder {
+    /// Add a field from a JSON value.
+    ///
+    /// Integers are written as `i` (or `u` above `i64::MAX`), other numbers as floats, strings
+    /// are quoted and bools kept. `null`, arrays and objects are reported by `build`.
+    ///
+    /// ```rust
+    /// use influx3_lp::LineBuilder;
+    /// use serde_json::json;
+    ///
+    /// let line = LineBuilder::new("home")
+    ///     .field_json("temp", &json!(21.5))
+    ///     .field_json("co", &json!(3))
+    ///     .field_json("weather", &json!("sunny"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(line, "home temp=21.5,co=3i,weather=\"sunny\"");
+    /// ```
+    pub fn field_json(mut self, key: &str, value: &Value) -> Self {
+        match value {
+            Value::Bool(b) => self.field(key, b),
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    self.field(key, &i)
+                } else if let Some(u) = n.as_u64() {
+                    self.field(key, &u)
+                } else {
+                    self.field(key, &n.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            Value::String(s) => self.field(key, s.as_str()),
+            Value::Null | Value::Array(_) | Value::Object(_) => {
+                if self.error.is_none() {
+                    self.error = Some(LpError::UnsupportedJson { field: key.to_string() });
+                }
+                self
+            }
+        }
+    }
+
+    /// Add every entry of a JSON object as a field, sorted by key.
+    ///
+    /// ```rust
+    /// use influx3_lp::LineBuilder;
+    /// use serde_json::json;
+    ///
+    /// let payload = json!({ "temp": 21.5, "co": 3 });
+    /// let line = LineBuilder::new("home")
+    ///     .fields_json(payload.as_object().unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(line, "home co=3i,temp=21.5");
+    /// ```
+    pub fn fields_json(self, object: &Map<String, Value>) -> Self {
+        let mut entries: Vec<_> = object.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter().fold(self, |builder, (key, value)| builder.field_json(key, value))
+    }
+}
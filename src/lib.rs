@@ -38,23 +38,1020 @@
 //! These are features implemented by `influx3_lp`:
 //! 
 //! * `#[influx3_lp(timestamp)]` attribute is optional
+//! * a timestamp member must be an integer or a type implementing `LpTimestamp`, like `SystemTime`, anything else is rejected at compile time
+//! * an integer timestamp which doesn't fit in an `i64`, like a `u64` above `i64::MAX`, is an `InvalidTimestamp` error
+//! * a `std::time::SystemTime` timestamp is written as the units of its precision since the Unix epoch, nanoseconds by default, one before the epoch is an `InvalidTimestamp` error
+//! * `#[influx3_lp(timestamp, precision = "ms")]` declares the unit of the timestamp, `lp_precision()` returns it as a `Precision` whose `as_query_value()` is the `precision` query parameter InfluxDB 3 expects
 //! * multiple `#[influx3_lp(tag)]` atrributes are supported
+//! * tags can be strings, integers, floats, `bool` or `char`, written with their string form and without type suffix, or any type implementing `LpTagValue`
 //! * empty tag is supported also
+//! * tags with an empty value are left out, as InfluxDB treats them as missing
+//! * `#[influx3_lp(tag, trim)]` strips leading and trailing whitespace from a tag value, leaving the tag out if nothing remains
+//! * `#[influx3_lp(tag, display)]` writes a tag value with its `Display`, for foreign types like `uuid::Uuid` which can't implement `LpTagValue`
+//! * an empty table name, or one starting with `_`, is rejected at compile time
+//! * names reserved by InfluxDB (`time`, `_time`) are rejected at compile time as table name, tag key or field key, and so are keys left empty by renaming
+//! * two members with the same key after renaming are rejected at compile time, a tag and a field included
 //! * tag values and field values are [escaped according to line protocol](https://docs.influxdata.com/influxdb3/core/reference/line-protocol/#special-characters)
-//! * field string has a length limit of 64K
-//! * `i8`,`i16`,`i32`,`i64` field values are appended with `i`
-//! * `u8`,`u16`,`u32`,`u64` field values are appended with `u`
-//! * field type of `Option<T>` is supported
+//! * field string has a length limit of 64K in bytes as enforced by InfluxDB, which `#[influx3_lp(max_field_len = 32768)]` changes for a whole struct and `#[influx3_lp(field, max_len = 256)]` for one field
+//! * `#[influx3_lp(len_unit = "chars")]` counts the length limit in characters instead of bytes, for a whole struct or one field
+//! * `#[influx3_lp(field, on_overflow = "truncate")]` cuts an over-long string field to the limit instead of failing
+//! * `#[influx3_lp(field, no_len_check)]` skips the length check of a string field known to be short, an oversize value is then written as it is and rejected by InfluxDB
+//! * `i8`,`i16`,`i32`,`i64`,`isize` field values are appended with `i`
+//! * `u8`,`u16`,`u32`,`u64`,`usize` field values are appended with `u`
+//! * `i128` and `u128` field values are written like `i64` and `u64`, values outside their range are rejected
+//! * `#[influx3_lp(unsigned = "forbid")]` writes unsigned fields as `i` integers for servers without the unsigned type, rejecting values above `i64::MAX`
+//! * `NonZeroU32` and the other `NonZero*` integers are written like the integer they wrap
+//! * field type of `Option<T>` is supported, nested options like `Option<Option<T>>` are left out when any layer is `None`
+//! * `#[influx3_lp(bool_format = "short")]` writes bool fields as `t` / `f` instead of `true` / `false`
+//! * NaN and infinite float field values are rejected
+//! * float field values are written in plain decimal notation, never with an exponent, subnormals included, and `-0.0` keeps its sign as `-0`
+//! * `#[influx3_lp(precision = 3)]` writes a float field with a fixed number of decimal places
+//! * `#[influx3_lp(field, float_style = "explicit")]` writes an integral float field as `21.0` instead of `21`
+//! * `#[influx3_lp(field, duration_unit = "ms")]` writes a `Duration` field as an integer count of `ns`, `us`, `ms` or `s`
+//! * `#[influx3_lp(field, as = "float")]` writes an integer field as a float to match an existing column, `as = "integer"` writes an integral float as an integer
+//! * newlines in tag values and string field values are rejected, line protocol can't escape them
+//! * `&str`, `Cow<str>`, `Box<str>`, `Arc<str>`, `Rc<str>` and `char` fields are written like `String`, and so are `Arc<String>` and `Rc<String>`
+//! * `IpAddr`, `Ipv4Addr` and `Ipv6Addr` are written as strings, quoted as fields and unquoted as tags
+//! * any type implementing `LpFieldValue` can be a field
+//! * enums with named-field variants are supported, each variant can have its own `table_name`
+//! * tuple structs are supported, `#[influx3_lp(names("x", "y"))]` gives the keys of their members
+//! * generic structs are supported, their bounds and where clauses are kept on the generated impls
+//! * `#[influx3_lp(fields)]` writes the entries of a map like `HashMap<String, f64>` as fields, sorted by key
+//! * `#[influx3_lp(tags)]` writes the entries of a map like `BTreeMap<String, String>` as tags, sorted by key and leaving out empty values
+//! * `#[influx3_lp(flatten)]` inlines the tags and fields of a member whose type also derives `Influx3Lp`
+//! * `#[influx3_lp(table_name = HOME)]` takes the measurement from a `&str` const, checked at compile time
+//! * `#[influx3_lp(table_name)]` on a `String` or `&str` member picks the measurement at runtime
+//! * `#[influx3_lp(skip)]` leaves a member out of the line
+//! * `#[influx3_lp(explode)]` on a `Vec` member writes one line per element, sharing the tags and timestamp, so `to_lp` may then return several lines
+//! * `#[influx3_lp(skip_if = "path::to::fn")]` leaves a field out when the predicate returns true for its value, like serde's `skip_serializing_if`
+//! * `#[influx3_lp(default = 0)]` writes a value for an `Option` field which is `None` instead of leaving it out, keeping the schema of a series stable
+//! * `#[influx3_lp(coalesce(key = "temp", primary, fallback))]` writes the first present of several `Option` fields under one key
+//! * field keys can be renamed with `#[influx3_lp(field, name = "temp")]`, tag keys with `#[influx3_lp(tag, name = "host")]`
+//! * `#[influx3_lp(field_prefix = "cpu_")]` and `field_suffix` namespace the keys of fields without an explicit `name`
+//! * `#[influx3_lp(rename_all = "camelCase")]` changes the casing of all keys without an explicit `name`, `"snake_case"`, `"PascalCase"` and `"kebab-case"` are supported too
+//! * tags and fields can follow the order of a shared schema, see below
+//! * `#[influx3_lp(sort_tags)]` writes tags sorted by key as InfluxDB recommends, off by default so tags keep their declaration order
+//! * `#[influx3_lp(sort_fields)]` writes fields sorted by key, off by default so fields keep their declaration order
+//! * tag values can be kept, dropped or replaced at runtime with a `TagGuard`
+//! * lines with keys only known at runtime can be built with `LineBuilder`
+//! * the escaping rules are public as `escape_measurement`, `escape_tag_key`, `escape_tag_value`, `escape_field_key` and `escape_field_value`, borrowing the input when nothing needs escaping, for parts of lines written by hand
+//! * a slice of points can be serialized into one body with `to_lp_batch`, or into one `String` per point with `to_lp_lines`
+//! * `lp_len_hint` gives the length of a line without building it, to keep batches under a size limit
+//! * `to_lp_cow` allocates the line once at its exact length, measured by a first pass
+//! * `write_lp` appends to a caller-provided `String`, so one buffer can be reused
+//! * `write_lp_line` appends a line followed by `\n`, so a loop builds a body with a trailing newline
+//! * extra tags known only at the call site can be added with `to_lp_with_tags`
+//! * `to_lp_with_timestamp` stamps a line with a timestamp given at the call site, overriding the `#[influx3_lp(timestamp)]` member
+//! * `write_lp_to` writes straight to any `std::io::Write`
+//! * `write_lp_batch_to` streams a slice of points as one body to any `std::io::Write`
+//! * `LpWriter` buffers pushed points and writes them to any `std::io::Write` in batches of at most `max_body_bytes`
+//! * `#[derive(FromLp)]` reads a struct back from a line it wrote, with `from_lp`, members with `flatten`, `as`, `duration_unit`, `default` or `explode` are rejected at compile time
+//! * lines can be parsed back into `LineParts` with `parse_line`, or streamed from a file with `lp_reader`
+//! * `lp_schema()` describes tags and fields, including `help` / `unit` metadata
+//! * `LpColumnCounts::TAG_COUNT` and `FIELD_COUNT` give the number of tags and fields of a type at compile time
+//! * `measurement()` returns the measurement of a value, static or taken from a member
+//! * without the default `std` feature the crate is `no_std` and only needs `alloc`, `write_lp_to`, `lp_reader`, `LpWriter` and the `wal` and `json` features need `std`
+//! * with the `wal` feature, points can be framed into a local write-ahead log with `append_wal` and recovered with `WalReader`
+//! * with the `serde` feature, any struct implementing `Serialize` can be written with `to_lp_serde`, honoring `#[serde(rename)]` and `#[serde(skip)]`
+//! * with the `json` feature, `LineBuilder` takes `serde_json::Value` fields with `field_json`, and the entries of a JSON object sorted by key with `fields_json`
+//! * with the `indexmap` feature, an `IndexMap` given to `#[influx3_lp(fields)]` or `#[influx3_lp(tags)]` keeps its insertion order instead of being sorted by key
+//!
+//! ### Ordering tags and fields from a schema file
+//!
+//! By default tags and fields are written in struct declaration order. To follow the column
+//! order of a schema shared with other languages, add `#[influx3_lp(order_from_env = "HOME_ORDER")]`
+//! to the struct. The `HOME_ORDER` environment variable is read when the macro expands and lists
+//! tag and field keys separated by commas. Keys not listed come last in declaration order and
+//! listed keys the struct doesn't have are ignored. The variable is usually set by `build.rs`:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     let order = std::fs::read_to_string("schema/home.order").unwrap();
+//!     println!("cargo:rustc-env=HOME_ORDER={}", order.trim());
+//!     println!("cargo:rerun-if-changed=schema/home.order");
+//! }
+//! ```
 
 ////////////////////////////////////////////////////////////////////////////////
 
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 pub use influx3_lp_macros::*;
 
+mod escape;
+mod parse;
+mod sink;
+mod value;
+
+pub use escape::{escape_field_key, escape_field_value, escape_measurement, escape_tag_key, escape_tag_value};
+pub use value::{LpFieldValue, LpTagValue, LpTimestamp};
+pub use parse::{parse_line, FieldValue, FromFieldValue, LineParts, LpParseError};
+#[cfg(feature = "std")]
+pub use parse::lp_reader;
+
+/// Used by the code generated by `#[derive(Influx3Lp)]`, not a public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::escape::{escape_measurement_into, escape_tag_key_into, escape_tag_value_into};
+    pub use alloc::borrow::Cow;
+    pub use alloc::format;
+    pub use alloc::string::String;
+    pub use crate::sink::{LenSink, LpSink};
+    #[cfg(feature = "std")]
+    pub use crate::sink::IoSink;
+    pub use crate::parse::{read_field_map, read_field_value, read_tag_map};
+    pub use crate::value::{FieldStyle, LimitedAny, LimitedStr, MaxLen, StrLimit, MAX_STRING_LEN};
+    pub use crate::value::{Styled, StyledAny, StyledBool, StyledUnsigned};
+    pub use crate::value::{AnyOrder, InsertionOrder, MapOrder};
+    pub use crate::value::{assert_measurement, check_measurement, check_tag_value, trim_tag_value, write_duration, write_field_map, write_tag_map, write_merged_tags, write_unique_fields, write_float_explicit, write_float_with_precision, LpFloat, LpNumber};
+
+    use crate::{KeepAll, LpColumn, LpColumnKind, LpError, TagGuard};
+
+    /// Call-time options of one serialization.
+    pub struct Context<'a> {
+        pub guard: &'a dyn TagGuard,
+        /// Written after the tags of the struct, unescaped.
+        pub extra_tags: &'a [(&'a str, &'a str)],
+        /// Written instead of the timestamp member, if any.
+        pub timestamp: Option<i64>,
+    }
+
+    impl Default for Context<'_> {
+        fn default() -> Self {
+            Context { guard: &KeepAll, extra_tags: &[], timestamp: None }
+        }
+    }
+
+    /// A line which doesn't match the struct `FromLp` reads it into.
+    pub fn parse_error(message: String) -> LpError {
+        LpError::Parse(crate::LpParseError::new(message))
+    }
+
+    /// The largest of the counts of the variants of an enum.
+    pub const fn max_count(counts: &[usize]) -> usize {
+        let mut max = 0;
+        let mut i = 0;
+        while i < counts.len() {
+            if counts[i] > max {
+                max = counts[i];
+            }
+            i += 1;
+        }
+        max
+    }
+
+    /// How many columns a flattened member adds to a schema, its timestamp is ignored.
+    pub const fn nested_len(columns: &[LpColumn]) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < columns.len() {
+            if !matches!(columns[i].kind, LpColumnKind::Timestamp) {
+                len += 1;
+            }
+            i += 1;
+        }
+        len
+    }
+
+    /// The schema of a type with flattened members, from its own columns and those of its
+    /// flattened members (`true`), in declaration order. `N` is the total from `nested_len`.
+    pub const fn concat_columns<const N: usize>(parts: &[(&[LpColumn], bool)]) -> [LpColumn; N] {
+        let mut columns = [LpColumn { key: "", kind: LpColumnKind::Field, help: None, unit: None }; N];
+        let mut n = 0;
+        let mut i = 0;
+        while i < parts.len() {
+            let (part, nested) = parts[i];
+            let mut j = 0;
+            while j < part.len() {
+                if !(nested && matches!(part[j].kind, LpColumnKind::Timestamp)) {
+                    columns[n] = part[j];
+                    n += 1;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        columns
+    }
+
+    /// Read a tag value with the `FromStr` implementation of its type.
+    pub fn parse_tag<T: core::str::FromStr>(key: &str, value: &str) -> Result<T, LpError> {
+        value.parse().map_err(|_| parse_error(alloc::format!("invalid value `{}` of tag `{}`", value, key)))
+    }
+}
+
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+pub use writer::LpWriter;
+#[cfg(feature = "wal")]
+mod wal;
+#[cfg(feature = "wal")]
+pub use wal::{append_wal, WalReader};
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "serde")]
+pub use ser::to_lp_serde;
+#[cfg(feature = "json")]
+mod json;
+
 /// This is the trait that `Influx3Lp` macro help us implementing.
 pub trait Influx3Lp {
     /// After decorating a struct with `#[derive(Influx3Lp)]` macro, we can call `to_lp` method directly to a line protocol string.
     ///
     /// Please pay attention: Influx table_name, tag keys and field keys are checked at compile time, but tag values and field values can only be checked at runtime. So please use valid tag/field values or panic will occur.
-    fn to_lp(&self) -> String;
+    ///
+    /// The string is a single line, unless a member is `#[influx3_lp(explode)]`, then it holds one line per element separated by `\n`, without a trailing newline.
+    fn to_lp(&self) -> String {
+        let mut buf = String::with_capacity(self.__size_hint());
+        self.write_lp(&mut buf);
+        buf
+    }
+
+    /// Same as `to_lp`, allocating the `String` once at the exact length of the line.
+    ///
+    /// A line always joins the measurement with at least one field, so it can't borrow from
+    /// `self` and is always `Cow::Owned`. The length is measured by a first pass without storing
+    /// anything, so the `String` never takes more memory than the one of `to_lp`, at the cost of
+    /// writing the line twice. `cargo bench --bench write_lp` compares both.
+    ///
+    /// ```rust
+    /// use influx3_lp::Influx3Lp;
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "home")]
+    /// struct SensorData {
+    ///     pub temp: f32,
+    /// }
+    ///
+    /// assert_eq!(SensorData { temp: 21.5 }.to_lp_cow(), "home temp=21.5");
+    /// ```
+    fn to_lp_cow(&self) -> Cow<'_, str>
+    where
+        Self: Sized,
+    {
+        let mut buf = String::with_capacity(self.lp_len_hint());
+        self.write_lp(&mut buf);
+        Cow::Owned(buf)
+    }
+
+    /// Same as `to_lp`, but invalid values are reported as an `LpError` instead of a panic.
+    fn try_to_lp(&self) -> Result<String, LpError> {
+        let mut buf = String::with_capacity(self.__size_hint());
+        self.__write_lp(&mut buf, &__private::Context::default())?;
+        Ok(buf)
+    }
+
+    /// Append the line protocol to `buf` without clearing it, so one allocation
+    /// can be reused across many points. No newline is appended.
+    ///
+    /// Panics on invalid values just like `to_lp`.
+    fn write_lp(&self, buf: &mut String) {
+        if let Err(e) = self.__write_lp(buf, &__private::Context::default()) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Same as `write_lp`, followed by a single `\n`, so appending many points gives a valid
+    /// body. The body then ends with a newline, which InfluxDB accepts.
+    ///
+    /// Panics on invalid values just like `to_lp`, `buf` is then left as it was.
+    ///
+    /// ```rust
+    /// use influx3_lp::Influx3Lp;
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "home")]
+    /// struct SensorData {
+    ///     pub temp: f32,
+    /// }
+    ///
+    /// let mut body = String::new();
+    /// for temp in [21.0, 22.5] {
+    ///     SensorData { temp }.write_lp_line(&mut body);
+    /// }
+    /// assert_eq!(body, "home temp=21\nhome temp=22.5\n");
+    /// ```
+    fn write_lp_line(&self, buf: &mut String) {
+        self.write_lp(buf);
+        buf.push('\n');
+    }
+
+    /// Same as `to_lp`, with extra tags only known at the call site, e.g. a trace id.
+    ///
+    /// `extra` holds unescaped keys and values, they are written after the tags of the struct, or
+    /// sorted among them with `#[influx3_lp(sort_tags)]`. Panics if an extra tag key is also
+    /// written by the struct, a flattened member or a tags map included, or is given twice.
+    fn to_lp_with_tags(&self, extra: &[(&str, &str)]) -> String
+    where
+        Self: Sized,
+    {
+        let mut buf = String::with_capacity(self.__size_hint());
+        let ctx = __private::Context { extra_tags: extra, ..Default::default() };
+        if let Err(e) = self.__write_lp(&mut buf, &ctx) {
+            panic!("{}", e);
+        }
+        buf
+    }
+
+    /// Same as `to_lp`, with the timestamp given at the call site, e.g. when replaying historical
+    /// data whose time isn't part of the record.
+    ///
+    /// `ts` takes precedence over the `#[influx3_lp(timestamp)]` member, which is then ignored,
+    /// and is written even if the member is `None`.
+    ///
+    /// ```rust
+    /// use influx3_lp::Influx3Lp;
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "home")]
+    /// struct SensorData {
+    ///     pub temp: f32,
+    /// }
+    ///
+    /// assert_eq!(SensorData { temp: 21.0 }.to_lp_with_timestamp(1735545600), "home temp=21 1735545600");
+    /// ```
+    fn to_lp_with_timestamp(&self, ts: i64) -> String {
+        let mut buf = String::with_capacity(self.__size_hint());
+        let ctx = __private::Context { timestamp: Some(ts), ..Default::default() };
+        if let Err(e) = self.__write_lp(&mut buf, &ctx) {
+            panic!("{}", e);
+        }
+        buf
+    }
+
+    /// Same as `to_lp`, but every tag is passed through `guard` before being written,
+    /// which can keep, drop or replace the tag value.
+    ///
+    /// Invalid field values are reported as an `LpError` instead of a panic.
+    fn to_lp_with_guard(&self, guard: &dyn TagGuard) -> Result<String, LpError> {
+        let mut buf = String::with_capacity(self.__size_hint());
+        self.write_lp_with_guard(&mut buf, guard)?;
+        Ok(buf)
+    }
+
+    /// Append the line protocol to `buf`, consulting `guard` for every tag.
+    ///
+    /// On error `buf` is left as it was before the call.
+    fn write_lp_with_guard(&self, buf: &mut String, guard: &dyn TagGuard) -> Result<(), LpError> {
+        self.__write_lp(buf, &__private::Context { guard, ..Default::default() })
+    }
+
+    /// The length in bytes of the line `to_lp` would build, escaping included, computed
+    /// without allocating the `String`. Useful to flush a batch before it exceeds the body size
+    /// limit of a write request.
+    ///
+    /// It's exact for valid values, when a value is invalid only the bytes before it are counted.
+    ///
+    /// ```rust
+    /// use influx3_lp::Influx3Lp;
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "home")]
+    /// struct SensorData {
+    ///     pub temp: f32,
+    ///     #[influx3_lp(tag)]
+    ///     pub room: String,
+    /// }
+    ///
+    /// let data = SensorData { temp: 21.5, room: String::from("Living Room") };
+    /// assert_eq!(data.lp_len_hint(), data.to_lp().len());
+    /// ```
+    fn lp_len_hint(&self) -> usize
+    where
+        Self: Sized,
+    {
+        let mut sink = __private::LenSink::default();
+        let _ = self.__write_line(&mut sink, &__private::Context::default());
+        __private::LpSink::len(&sink)
+    }
+
+    /// Generated by `#[derive(Influx3Lp)]`, a rough estimate of the line length computed from the
+    /// keys known at compile time, used to size the `String` of `to_lp` and friends.
+    #[doc(hidden)]
+    fn __size_hint(&self) -> usize {
+        0
+    }
+
+    /// Generated by `#[derive(Influx3Lp)]`, append the `,key=value` tags of the struct.
+    /// Also used to inline a `#[influx3_lp(flatten)]` member into its parent.
+    #[doc(hidden)]
+    fn __write_tags<B: __private::LpSink + ?Sized>(&self, buf: &mut B, ctx: &__private::Context<'_>) -> Result<(), LpError>
+    where
+        Self: Sized;
+
+    /// Generated by `#[derive(Influx3Lp)]`, append the fields of the struct, the first one is
+    /// preceded by a space if nothing was written since `fields_start`, the others by a comma.
+    #[doc(hidden)]
+    fn __write_fields<B: __private::LpSink + ?Sized>(&self, buf: &mut B, fields_start: usize) -> Result<(), LpError>
+    where
+        Self: Sized;
+
+    /// Generated by `#[derive(Influx3Lp)]`, write the whole line to any output target.
+    #[doc(hidden)]
+    fn __write_line<B: __private::LpSink + ?Sized>(&self, buf: &mut B, ctx: &__private::Context<'_>) -> Result<(), LpError>
+    where
+        Self: Sized;
+
+    /// Generated by `#[derive(Influx3Lp)]`, the `String` methods are built on it.
+    /// On error `buf` is left as it was before the call.
+    #[doc(hidden)]
+    fn __write_lp(&self, buf: &mut String, ctx: &__private::Context<'_>) -> Result<(), LpError>;
+
+    /// Write the line protocol straight to `w`, without building a `String` first.
+    /// No newline is appended.
+    ///
+    /// Each part of the line is written separately, so wrap unbuffered writers like
+    /// files or sockets in a `std::io::BufWriter`. Invalid values are reported as an
+    /// `InvalidData` error, in which case part of the line may already be written.
+    #[cfg(feature = "std")]
+    fn write_lp_to<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()>
+    where
+        Self: Sized,
+    {
+        let mut sink = __private::IoSink::new(w);
+        let result = self.__write_line(&mut sink, &__private::Context::default());
+        sink.finish(result)
+    }
+
+    /// The measurement this value is written to, unescaped. It's the `table_name` of the struct
+    /// or enum variant, or the value of the `#[influx3_lp(table_name)]` member.
+    ///
+    /// ```rust
+    /// use influx3_lp::Influx3Lp;
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "home")]
+    /// struct SensorData {
+    ///     pub temp: f32,
+    /// }
+    ///
+    /// assert_eq!(SensorData { temp: 21.0 }.measurement(), "home");
+    /// ```
+    fn measurement(&self) -> &str;
+
+    /// Describe the tags, fields and timestamp of this type in declaration order,
+    /// including the `help` / `unit` metadata given with `#[influx3_lp(help = "...", unit = "...")]`.
+    ///
+    /// The columns of a flattened member take its place, without its timestamp, unless its type
+    /// depends on a type parameter, a static schema can't. The entries of a map aren't known
+    /// until runtime and are left out.
+    ///
+    /// ```rust
+    /// use influx3_lp::{Influx3Lp, LpColumnKind};
+    ///
+    /// #[derive(Influx3Lp)]
+    /// #[influx3_lp(table_name = "host")]
+    /// struct Memory {
+    ///     #[influx3_lp(help = "Resident memory", unit = "bytes")]
+    ///     pub rss: u64,
+    ///     #[influx3_lp(tag)]
+    ///     pub host: String,
+    /// }
+    ///
+    /// let schema = Memory::lp_schema();
+    /// assert_eq!(schema[0].key, "rss");
+    /// assert_eq!(schema[0].kind, LpColumnKind::Field);
+    /// assert_eq!(schema[0].unit, Some("bytes"));
+    /// assert_eq!(schema[1].kind, LpColumnKind::Tag);
+    /// ```
+    fn lp_schema() -> &'static [LpColumn]
+    where
+        Self: Sized;
+
+    /// The unit of the timestamps of this type, from `#[influx3_lp(timestamp, precision = "ms")]`,
+    /// nanoseconds by default. See `Precision`.
+    fn lp_precision() -> Precision
+    where
+        Self: Sized,
+    {
+        Precision::Nanos
+    }
+}
+
+/// How many tags and fields a line of this type holds at most, implemented by `#[derive(Influx3Lp)]`.
+///
+/// Optional members are counted, and so are the tags and fields of a flattened member, the
+/// entries of a `#[influx3_lp(fields)]` or `#[influx3_lp(tags)]` map aren't. An enum has the counts of its largest variant.
+/// It's a trait of its own since associated consts would rule out `dyn Influx3Lp`.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, LpColumnCounts};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+///     pub co: Option<i32>,
+///     #[influx3_lp(tag)]
+///     pub room: String,
+///     #[influx3_lp(timestamp)]
+///     pub time: i64,
+/// }
+///
+/// assert_eq!(SensorData::TAG_COUNT, 1);
+/// assert_eq!(SensorData::FIELD_COUNT, 2);
+/// ```
+pub trait LpColumnCounts {
+    const TAG_COUNT: usize;
+    const FIELD_COUNT: usize;
+    /// `Influx3Lp::lp_schema` as a const, so the schema of a flattened member can be inlined.
+    #[doc(hidden)]
+    const __SCHEMA: &'static [LpColumn] = &[];
+}
+
+/// Read a struct back from one line, implemented by `#[derive(FromLp)]`.
+///
+/// The derive shares the `#[influx3_lp(...)]` attributes of `Influx3Lp`, so a struct deriving
+/// both reads what it writes. Escaping and type suffixes are reversed, tags are read with
+/// `FromStr` and fields with `FromFieldValue`, unsigned fields also from the `i` integers of
+/// `unsigned = "forbid"`. The line must have the same measurement, no unknown keys and every
+/// non-`Option` member, skipped members get their `Default`.
+///
+/// A member with `flatten`, `as`, `duration_unit`, `default` or `explode` is a compile error, what
+/// they write can't be read back as it was. Values changed on the way out stay changed: a float
+/// rounded by `precision`, a string cut by `on_overflow = "truncate"` or a tag stripped by `trim`.
+/// A member left out by `skip_if`, or a tag left out because it's empty, reads back only into an
+/// `Option`.
+///
+/// ```rust
+/// use influx3_lp::{FromLp, Influx3Lp};
+///
+/// #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f64,
+///     #[influx3_lp(tag)]
+///     pub room: String,
+/// }
+///
+/// let data = SensorData::from_lp("home,room=Living\\ Room temp=21.5").unwrap();
+/// assert_eq!(data, SensorData { temp: 21.5, room: String::from("Living Room") });
+/// assert_eq!(SensorData::from_lp(&data.to_lp()).unwrap(), data);
+/// ```
+pub trait FromLp: Sized {
+    /// Parse a single line, without trailing newline.
+    fn from_lp(line: &str) -> Result<Self, LpError>;
+}
+
+/// Unit of the timestamps of a line, as declared by `#[influx3_lp(timestamp, precision = "ms")]`.
+///
+/// The server must be told the same unit with the `precision` query parameter of the write
+/// request, `as_query_value` gives its value.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, Precision};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+///     #[influx3_lp(timestamp, precision = "ms")]
+///     pub timestamp: i64,
+/// }
+///
+/// let url = format!("/api/v3/write_lp?db=sensors&precision={}", SensorData::lp_precision().as_query_value());
+/// assert_eq!(url, "/api/v3/write_lp?db=sensors&precision=millisecond");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precision {
+    Seconds,
+    Millis,
+    Micros,
+    /// InfluxDB's default.
+    #[default]
+    Nanos,
+}
+
+impl Precision {
+    /// The value of the `precision` query parameter of InfluxDB 3.
+    pub fn as_query_value(&self) -> &'static str {
+        match self {
+            Precision::Seconds => "second",
+            Precision::Millis => "millisecond",
+            Precision::Micros => "microsecond",
+            Precision::Nanos => "nanosecond",
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn nanos(&self) -> u128 {
+        match self {
+            Precision::Seconds => 1_000_000_000,
+            Precision::Millis => 1_000_000,
+            Precision::Micros => 1_000,
+            Precision::Nanos => 1,
+        }
+    }
+}
+
+/// What a struct member becomes in line protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LpColumnKind {
+    Tag,
+    Field,
+    Timestamp,
+}
+
+/// Description of one struct member, as returned by `Influx3Lp::lp_schema`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LpColumn {
+    /// Tag or field key, unescaped.
+    pub key: &'static str,
+    pub kind: LpColumnKind,
+    /// Free text documentation from `#[influx3_lp(help = "...")]`.
+    pub help: Option<&'static str>,
+    /// Unit from `#[influx3_lp(unit = "...")]`, e.g. `"bytes"`.
+    pub unit: Option<&'static str>,
+}
+
+/// Serialize many points into one line protocol body, lines are separated by `\n`
+/// without a trailing newline.
+///
+/// The output buffer is pre-sized from the length of the first line.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, to_lp_batch};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+/// }
+///
+/// let points = vec![SensorData { temp: 21.0 }, SensorData { temp: 22.5 }];
+/// assert_eq!(to_lp_batch(&points), "home temp=21\nhome temp=22.5");
+/// ```
+pub fn to_lp_batch<T: Influx3Lp>(items: &[T]) -> String {
+    let mut iter = items.iter();
+    let Some(first) = iter.next() else {
+        return String::new();
+    };
+
+    let mut body = String::new();
+    first.write_lp(&mut body);
+    // assume every line is about as long as the first one
+    body.reserve((body.len() + 1) * (items.len() - 1));
+    for item in iter {
+        body.push('\n');
+        item.write_lp(&mut body);
+    }
+    body
+}
+
+/// Serialize many points into one `String` each, for processing lines one by one.
+/// Panics on invalid values just like `to_lp`.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, to_lp_lines};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+/// }
+///
+/// let points = vec![SensorData { temp: 21.0 }, SensorData { temp: 22.5 }];
+/// assert_eq!(to_lp_lines(&points), ["home temp=21", "home temp=22.5"]);
+/// ```
+pub fn to_lp_lines<T: Influx3Lp>(items: &[T]) -> Vec<String> {
+    let mut lines = Vec::with_capacity(items.len());
+    lines.extend(items.iter().map(Influx3Lp::to_lp));
+    lines
+}
+
+/// Stream many points to `w` as one line protocol body, lines are separated by `\n`
+/// without a trailing newline, like `to_lp_batch`.
+///
+/// The first io error, or invalid value reported as `InvalidData`, stops the batch. Wrap
+/// unbuffered writers in a `std::io::BufWriter`.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, write_lp_batch_to};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+/// }
+///
+/// let mut body = Vec::new();
+/// write_lp_batch_to(&[SensorData { temp: 21.0 }, SensorData { temp: 22.5 }], &mut body).unwrap();
+/// assert_eq!(body, b"home temp=21\nhome temp=22.5");
+/// ```
+#[cfg(feature = "std")]
+pub fn write_lp_batch_to<T: Influx3Lp, W: std::io::Write>(items: &[T], w: &mut W) -> std::io::Result<()> {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b"\n")?;
+        }
+        item.write_lp_to(w)?;
+    }
+    Ok(())
+}
+
+/// Build one line at runtime, for data without a fixed struct, e.g. with keys read
+/// from a config file.
+///
+/// The measurement, keys and values are escaped and checked like in `#[derive(Influx3Lp)]`, tags
+/// and fields are written in the order they are added. The first invalid one is reported by `build`.
+///
+/// ```rust
+/// use influx3_lp::LineBuilder;
+///
+/// let line = LineBuilder::new("home")
+///     .tag("room", "Living Room")
+///     .field_int("count", 3)
+///     .field_float("temp", 21.0)
+///     .field_str("msg", "hi")
+///     .timestamp(123)
+///     .build()
+///     .unwrap();
+/// assert_eq!(line, "home,room=Living\\ Room count=3i,temp=21,msg=\"hi\" 123");
+/// ```
+#[derive(Debug, Clone)]
+pub struct LineBuilder {
+    measurement: String,
+    /// escaped `,key=value` pairs
+    tags: String,
+    /// escaped `key=value` pairs separated by `,`
+    fields: String,
+    timestamp: Option<i64>,
+    error: Option<LpError>,
+}
+
+impl LineBuilder {
+    pub fn new(measurement: &str) -> Self {
+        LineBuilder {
+            measurement: escape::escape_measurement(measurement).into_owned(),
+            tags: String::new(),
+            fields: String::new(),
+            timestamp: None,
+            error: value::check_measurement(measurement).err(),
+        }
+    }
+
+    /// Add a tag, it's left out if `value` is empty.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Err(e) = value::check_key(key) {
+            self.error = Some(e);
+            return self;
+        }
+        if value.is_empty() {
+            return self;
+        }
+        if let Err(e) = value::check_tag_value(key, value) {
+            self.error = Some(e);
+            return self;
+        }
+        self.tags.push(',');
+        escape::escape_tag_key_into(key, &mut self.tags);
+        self.tags.push('=');
+        escape::escape_tag_value_into(value, &mut self.tags);
+        self
+    }
+
+    /// Add a field of any type implementing `LpFieldValue`.
+    pub fn field<V: LpFieldValue + ?Sized>(mut self, key: &str, value: &V) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Err(e) = value::check_key(key) {
+            self.error = Some(e);
+            return self;
+        }
+        let start = self.fields.len();
+        if start > 0 {
+            self.fields.push(',');
+        }
+        escape::escape_field_key_into(key, &mut self.fields);
+        self.fields.push('=');
+        if let Err(e) = value.write_field(&mut self.fields, "") {
+            self.fields.truncate(start);
+            self.error = Some(e.with_field(key));
+        }
+        self
+    }
+
+    pub fn field_int(self, key: &str, value: i64) -> Self {
+        self.field(key, &value)
+    }
+
+    pub fn field_uint(self, key: &str, value: u64) -> Self {
+        self.field(key, &value)
+    }
+
+    pub fn field_float(self, key: &str, value: f64) -> Self {
+        self.field(key, &value)
+    }
+
+    pub fn field_str(self, key: &str, value: &str) -> Self {
+        self.field(key, value)
+    }
+
+    pub fn field_bool(self, key: &str, value: bool) -> Self {
+        self.field(key, &value)
+    }
+
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Join the parts into a line, without trailing newline.
+    pub fn build(self) -> Result<String, LpError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.fields.is_empty() {
+            return Err(LpError::EmptyFieldSet);
+        }
+
+        let mut line = self.measurement;
+        line.push_str(&self.tags);
+        line.push(' ');
+        line.push_str(&self.fields);
+        if let Some(timestamp) = self.timestamp {
+            line.push(' ');
+            line.push_str(&timestamp.to_string());
+        }
+        Ok(line)
+    }
+}
+
+/// Decision made by a `TagGuard` for one tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagDecision {
+    /// Write the tag value unchanged.
+    Keep,
+    /// Leave the tag out of the line.
+    Drop,
+    /// Write this value instead, e.g. a bucket name like `"other"`.
+    Replace(String),
+}
+
+/// A callback deciding whether a tag value may be written.
+///
+/// This is a safety valve against cardinality blowups caused by dynamic tag values:
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, TagDecision, TagGuard};
+///
+/// struct KnownRooms;
+///
+/// impl TagGuard for KnownRooms {
+///     fn check(&self, key: &str, value: &str) -> TagDecision {
+///         match (key, value) {
+///             ("room", "Kitchen" | "Bedroom") => TagDecision::Keep,
+///             ("room", _) => TagDecision::Replace(String::from("other")),
+///             _ => TagDecision::Keep,
+///         }
+///     }
+/// }
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+///     #[influx3_lp(tag)]
+///     pub room: String,
+/// }
+///
+/// let data = SensorData { temp: 21.0, room: String::from("Garage") };
+/// assert_eq!(data.to_lp_with_guard(&KnownRooms).unwrap(), "home,room=other temp=21");
+/// ```
+pub trait TagGuard {
+    /// Called with the unescaped tag key (after `name` renaming) and tag value.
+    fn check(&self, key: &str, value: &str) -> TagDecision;
+}
+
+/// The guard used by `to_lp`, which keeps every tag.
+pub struct KeepAll;
+
+impl TagGuard for KeepAll {
+    fn check(&self, _key: &str, _value: &str) -> TagDecision {
+        TagDecision::Keep
+    }
+}
+
+/// Errors reported while serializing to line protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LpError {
+    /// A string field value exceeds the 64K limit, or the one set by `max_len` / `max_field_len`.
+    FieldTooLong { field: String },
+    /// A float field value is NaN or infinite, which line protocol can't represent.
+    InvalidFloat { field: String },
+    /// A tag value contains a newline or carriage return.
+    InvalidTagValue { tag: String },
+    /// A string field value contains a newline or carriage return.
+    InvalidFieldValue { field: String },
+    /// An `i128` or `u128` field value doesn't fit in the 64 bit integers of InfluxDB.
+    IntegerOutOfRange { field: String },
+    /// A float written with `#[influx3_lp(as = "integer")]` has a fractional part, or is NaN or infinite.
+    NotAnInteger { field: String },
+    /// Every field is `None`, or the `#[influx3_lp(explode)]` member is empty, but a line needs at least one field.
+    EmptyFieldSet,
+    /// A measurement taken from a `#[influx3_lp(table_name)]` member is empty, starts with `_`
+    /// or contains a newline.
+    InvalidMeasurement { name: String },
+    /// A timestamp doesn't fit in the 64 bit signed integers of InfluxDB, or a `SystemTime` one is
+    /// before the Unix epoch.
+    InvalidTimestamp,
+    /// A tag or field key given to `LineBuilder` is empty, reserved by InfluxDB or contains a newline.
+    InvalidKey { key: String },
+    /// A tag key is written twice, e.g. by an extra tag given to `to_lp_with_tags` and a member.
+    DuplicateTagKey { tag: String },
+    /// A field key is written twice, e.g. by a member and a flattened member.
+    DuplicateFieldKey { field: String },
+    /// A line read by `FromLp` is invalid or doesn't match the struct.
+    Parse(LpParseError),
+    /// A value given to `to_lp_serde` has no line protocol representation.
+    #[cfg(feature = "serde")]
+    Serde(String),
+    /// A JSON `null`, array or object given to `LineBuilder::field_json`.
+    #[cfg(feature = "json")]
+    UnsupportedJson { field: String },
+}
+
+impl core::fmt::Display for LpError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LpError::FieldTooLong { field } => {
+                write!(f, "Length of string field value has a limit of 64K, or the one set by `max_len` (field `{}`)", field)
+            }
+            LpError::InvalidFloat { field } => {
+                write!(f, "Float field value must be finite, NaN and infinity are not supported (field `{}`)", field)
+            }
+            LpError::InvalidTagValue { tag } => {
+                write!(f, "Tag value must not contain a newline (tag `{}`)", tag)
+            }
+            LpError::InvalidFieldValue { field } => {
+                write!(f, "String field value must not contain a newline (field `{}`)", field)
+            }
+            LpError::IntegerOutOfRange { field } => {
+                write!(f, "Integer field value must fit in 64 bits (field `{}`)", field)
+            }
+            LpError::NotAnInteger { field } => {
+                write!(f, "Field value written as integer must be integral (field `{}`)", field)
+            }
+            LpError::EmptyFieldSet => {
+                write!(f, "At least one field must have a value")
+            }
+            LpError::InvalidMeasurement { name } => {
+                write!(f, "Measurement `{}` must not be empty, start with `_` or contain a newline", name)
+            }
+            LpError::InvalidTimestamp => {
+                write!(f, "Timestamp must fit in a signed 64 bit integer, and a `SystemTime` must not be before the Unix epoch")
+            }
+            LpError::InvalidKey { key } => {
+                write!(f, "Key `{}` must not be empty, `time` or contain a newline", key)
+            }
+            LpError::DuplicateTagKey { tag } => {
+                write!(f, "Tag key `{}` is used more than once", tag)
+            }
+            LpError::DuplicateFieldKey { field } => {
+                write!(f, "Field key `{}` is used more than once", field)
+            }
+            LpError::Parse(e) => {
+                write!(f, "{}", e)
+            }
+            #[cfg(feature = "serde")]
+            LpError::Serde(message) => {
+                write!(f, "{}", message)
+            }
+            #[cfg(feature = "json")]
+            LpError::UnsupportedJson { field } => {
+                write!(f, "JSON null, arrays and objects can't be field values (field `{}`)", field)
+            }
+        }
+    }
+}
+
+impl LpError {
+    /// Name the field of a value written without knowing its key.
+    pub(crate) fn with_field(self, key: &str) -> Self {
+        match self {
+            LpError::FieldTooLong { .. } => LpError::FieldTooLong { field: key.to_string() },
+            LpError::InvalidFloat { .. } => LpError::InvalidFloat { field: key.to_string() },
+            LpError::InvalidFieldValue { .. } => LpError::InvalidFieldValue { field: key.to_string() },
+            LpError::IntegerOutOfRange { .. } => LpError::IntegerOutOfRange { field: key.to_string() },
+            LpError::NotAnInteger { .. } => LpError::NotAnInteger { field: key.to_string() },
+            e => e,
+        }
+    }
 }
 
+impl core::error::Error for LpError {}
@@ -0,0 +1,355 @@
+//! Parsing line protocol back into its parts.
+
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::num::{NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize};
+use core::num::{NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize};
+
+/// A field value with its line protocol type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    /// written with the `i` suffix
+    Integer(i64),
+    /// written with the `u` suffix
+    UInteger(u64),
+    String(String),
+    Boolean(bool),
+}
+
+/// A type which can be read back from a field value, the counterpart of `LpFieldValue`.
+///
+/// Returns `None` if `value` has another line protocol type, e.g. a float for an integer field.
+pub trait FromFieldValue: Sized {
+    fn from_field_value(value: &FieldValue) -> Option<Self>;
+}
+
+macro_rules! impl_from_field_value {
+    ($variant:ident: $($ty:ty),*) => {
+        $(
+            impl FromFieldValue for $ty {
+                fn from_field_value(value: &FieldValue) -> Option<Self> {
+                    match value {
+                        FieldValue::$variant(v) => <$ty>::try_from(*v).ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_field_value!(Integer: i8, i16, i32, i64, i128, isize);
+impl_from_field_value!(UInteger: u8, u16, u32, u64, u128, usize);
+impl_from_field_value!(Boolean: bool);
+impl_from_field_value!(Float: f64);
+
+macro_rules! impl_non_zero_from_field_value {
+    ($($ty:ty => $inner:ty),*) => {
+        $(
+            impl FromFieldValue for $ty {
+                fn from_field_value(value: &FieldValue) -> Option<Self> {
+                    <$ty>::new(<$inner>::from_field_value(value)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_non_zero_from_field_value!(NonZeroI8 => i8, NonZeroI16 => i16, NonZeroI32 => i32, NonZeroI64 => i64,
+                                NonZeroI128 => i128, NonZeroIsize => isize);
+impl_non_zero_from_field_value!(NonZeroU8 => u8, NonZeroU16 => u16, NonZeroU32 => u32, NonZeroU64 => u64,
+                                NonZeroU128 => u128, NonZeroUsize => usize);
+
+impl FromFieldValue for f32 {
+    fn from_field_value(value: &FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::Float(v) => Some(*v as f32),
+            _ => None,
+        }
+    }
+}
+
+impl FromFieldValue for String {
+    fn from_field_value(value: &FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::String(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromFieldValue for char {
+    fn from_field_value(value: &FieldValue) -> Option<Self> {
+        match value {
+            FieldValue::String(v) => {
+                let mut chars = v.chars();
+                chars.next().filter(|_| chars.next().is_none())
+            }
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_ip_from_field_value {
+    ($($ty:ty),*) => {
+        $(
+            impl FromFieldValue for $ty {
+                fn from_field_value(value: &FieldValue) -> Option<Self> {
+                    match value {
+                        FieldValue::String(v) => v.parse().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_ip_from_field_value!(IpAddr, Ipv4Addr, Ipv6Addr);
+
+/// Read a field value, an unsigned one also from the `i` integer `unsigned = "forbid"` writes
+/// when `signed`.
+pub fn read_field_value<T: FromFieldValue>(value: &FieldValue, signed: bool) -> Option<T> {
+    T::from_field_value(value).or_else(|| match value {
+        FieldValue::Integer(v) if signed => T::from_field_value(&FieldValue::UInteger(u64::try_from(*v).ok()?)),
+        _ => None,
+    })
+}
+
+/// Insert a field into the map of a `#[influx3_lp(fields)]` member.
+pub fn read_field_map<M, K, V>(map: &mut M, key: String, value: &FieldValue, signed: bool) -> Result<(), crate::LpError>
+where
+    M: IntoIterator<Item = (K, V)> + Extend<(K, V)>,
+    K: From<String>,
+    V: FromFieldValue,
+{
+    let Some(value) = read_field_value(value, signed) else {
+        return Err(crate::__private::parse_error(format!("field `{}` has the wrong type", key)));
+    };
+    map.extend(Some((K::from(key), value)));
+    Ok(())
+}
+
+/// Insert a tag into the map of a `#[influx3_lp(tags)]` member.
+pub fn read_tag_map<M, K, V>(map: &mut M, key: String, value: &str) -> Result<(), crate::LpError>
+where
+    M: IntoIterator<Item = (K, V)> + Extend<(K, V)>,
+    K: From<String>,
+    V: core::str::FromStr,
+{
+    let value = crate::__private::parse_tag(&key, value)?;
+    map.extend(Some((K::from(key), value)));
+    Ok(())
+}
+
+/// One parsed line, keys and values are unescaped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineParts {
+    pub measurement: String,
+    pub tags: Vec<(String, String)>,
+    pub fields: Vec<(String, FieldValue)>,
+    pub timestamp: Option<i64>,
+}
+
+/// A line which isn't valid line protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LpParseError {
+    /// 1-based line number, always 1 for `parse_line`
+    pub line: usize,
+    pub message: String,
+}
+
+impl LpParseError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        LpParseError { line: 1, message: message.into() }
+    }
+}
+
+impl fmt::Display for LpParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for LpParseError {}
+
+/// Take an escaped element from the start of `s`, up to the first unescaped char of `stops`.
+///
+/// A backslash followed by one of `escaped` stands for that char, any other backslash is literal.
+/// Returns the unescaped element and the rest of `s`, starting with the stop char.
+fn take_escaped<'a>(s: &'a str, stops: &[char], escaped: &[char]) -> (String, &'a str) {
+    let mut out = String::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            if let Some(&(_, next)) = chars.peek()
+                && escaped.contains(&next)
+            {
+                out.push(next);
+                chars.next();
+                continue;
+            }
+        } else if stops.contains(&c) {
+            return (out, &s[i..]);
+        }
+        out.push(c);
+    }
+    (out, "")
+}
+
+/// Take a `key=` from the start of `s`, returning the unescaped key and what follows `=`.
+fn take_key<'a>(s: &'a str, what: &str) -> Result<(String, &'a str), LpParseError> {
+    let (key, rest) = take_escaped(s, &[',', ' ', '='], &[',', ' ', '=', '\\']);
+    if key.is_empty() {
+        return Err(LpParseError::new(format!("empty {} key", what)));
+    }
+    match rest.strip_prefix('=') {
+        Some(rest) => Ok((key, rest)),
+        None => Err(LpParseError::new(format!("missing `=` after {} key `{}`", what, key))),
+    }
+}
+
+/// Take a field value from the start of `s`, returning it and the rest of `s`.
+fn take_field_value<'a>(s: &'a str, key: &str) -> Result<(FieldValue, &'a str), LpParseError> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, next @ ('"' | '\\'))) => value.push(next),
+                    Some((_, next)) => {
+                        value.push('\\');
+                        value.push(next);
+                    }
+                    None => value.push('\\'),
+                },
+                '"' => return Ok((FieldValue::String(value), &quoted[i + 1..])),
+                _ => value.push(c),
+            }
+        }
+        return Err(LpParseError::new(format!("unterminated string value of field `{}`", key)));
+    }
+
+    let end = s.find([',', ' ']).unwrap_or(s.len());
+    let (text, rest) = s.split_at(end);
+    let invalid = || LpParseError::new(format!("invalid value `{}` of field `{}`", text, key));
+    let value = match text {
+        "t" | "T" | "true" | "True" | "TRUE" => FieldValue::Boolean(true),
+        "f" | "F" | "false" | "False" | "FALSE" => FieldValue::Boolean(false),
+        _ => {
+            if let Some(int) = text.strip_suffix('i') {
+                FieldValue::Integer(int.parse().map_err(|_| invalid())?)
+            } else if let Some(uint) = text.strip_suffix('u') {
+                FieldValue::UInteger(uint.parse().map_err(|_| invalid())?)
+            } else {
+                // reject what Rust parses but line protocol doesn't know
+                if text.is_empty() || text.contains(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E') {
+                    return Err(invalid());
+                }
+                FieldValue::Float(text.parse().map_err(|_| invalid())?)
+            }
+        }
+    };
+    Ok((value, rest))
+}
+
+/// Parse a single line of line protocol, without trailing newline.
+///
+/// ```rust
+/// use influx3_lp::{parse_line, FieldValue};
+///
+/// let parts = parse_line("home,room=Living\\ Room temp=21,co=0i,weather=\"sunny\" 1735545600").unwrap();
+/// assert_eq!(parts.measurement, "home");
+/// assert_eq!(parts.tags, vec![(String::from("room"), String::from("Living Room"))]);
+/// assert_eq!(parts.fields[1], (String::from("co"), FieldValue::Integer(0)));
+/// assert_eq!(parts.timestamp, Some(1735545600));
+/// ```
+pub fn parse_line(line: &str) -> Result<LineParts, LpParseError> {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let (measurement, mut rest) = take_escaped(line, &[',', ' '], &[',', ' ', '\\']);
+    if measurement.is_empty() {
+        return Err(LpParseError::new("missing measurement"));
+    }
+
+    let mut tags = Vec::new();
+    while let Some(tag) = rest.strip_prefix(',') {
+        let (key, value_start) = take_key(tag, "tag")?;
+        let (value, after) = take_escaped(value_start, &[',', ' '], &[',', ' ', '=', '\\']);
+        if value.is_empty() {
+            return Err(LpParseError::new(format!("empty value of tag `{}`", key)));
+        }
+        tags.push((key, value));
+        rest = after;
+    }
+
+    let Some(mut rest) = rest.strip_prefix(' ') else {
+        return Err(LpParseError::new("missing field set"));
+    };
+    let mut fields = Vec::new();
+    loop {
+        let (key, value_start) = take_key(rest, "field")?;
+        let (value, after) = take_field_value(value_start, &key)?;
+        fields.push((key, value));
+        match after.strip_prefix(',') {
+            Some(next) => rest = next,
+            None => {
+                rest = after;
+                break;
+            }
+        }
+    }
+
+    let timestamp = match rest.strip_prefix(' ') {
+        Some(ts) => Some(ts.parse().map_err(|_| LpParseError::new(format!("invalid timestamp `{}`", ts)))?),
+        None if rest.is_empty() => None,
+        None => return Err(LpParseError::new(format!("unexpected `{}` after field set", rest))),
+    };
+
+    Ok(LineParts { measurement, tags, fields, timestamp })
+}
+
+/// Lazily parse line protocol read from `r`, one line at a time.
+///
+/// Blank lines and comments starting with `#` are skipped. Errors carry the line number
+/// and iteration continues with the next line, an io error ends the iteration.
+///
+/// ```rust
+/// use influx3_lp::lp_reader;
+///
+/// let body = "# exported\nhome temp=21\n\nhome temp=22.5\n";
+/// let lines: Vec<_> = lp_reader(body.as_bytes()).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(lines.len(), 2);
+/// ```
+#[cfg(feature = "std")]
+pub fn lp_reader<R: std::io::BufRead>(r: R) -> impl Iterator<Item = Result<LineParts, LpParseError>> {
+    let mut lines = r.lines().enumerate();
+    let mut failed = false;
+    core::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        for (i, line) in lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(LpParseError { line: i + 1, message: e.to_string() }));
+                }
+            };
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            return Some(parse_line(&line).map_err(|e| LpParseError { line: i + 1, ..e }));
+        }
+        None
+    })
+}
@@ -0,0 +1,319 @@
+//! Serializing any `serde::Serialize` struct to line protocol, without the derive macro.
+//!
+//! Every member of the struct becomes a field, `#[serde(rename)]` and `#[serde(skip)]` are
+//! honored by serde itself. Members which are `None` or `()` are left out.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::escape::{escape_field_key_into, escape_measurement_into};
+use crate::value::check_measurement;
+use crate::{LpError, LpFieldValue};
+
+/// Serialize the struct `value` into a line of `measurement`, without tags and timestamp.
+///
+/// ```rust
+/// use serde::Serialize;
+/// use influx3_lp::to_lp_serde;
+///
+/// #[derive(Serialize)]
+/// struct SensorData {
+///     #[serde(rename = "temperature")]
+///     temp: f64,
+///     co: i32,
+///     weather: Option<String>,
+///     #[serde(skip)]
+///     cache: Vec<u8>,
+/// }
+///
+/// let data = SensorData { temp: 21.5, co: 0, weather: None, cache: Vec::new() };
+/// assert_eq!(to_lp_serde("home", &data).unwrap(), "home temperature=21.5,co=0i");
+/// ```
+pub fn to_lp_serde<T: Serialize + ?Sized>(measurement: &str, value: &T) -> Result<String, LpError> {
+    check_measurement(measurement)?;
+    let mut line = String::new();
+    escape_measurement_into(measurement, &mut line);
+    let fields_start = line.len();
+    value.serialize(LineSerializer { line: &mut line, fields_start })?;
+    if line.len() == fields_start {
+        return Err(LpError::EmptyFieldSet);
+    }
+    Ok(line)
+}
+
+impl ser::Error for LpError {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        LpError::Serde(msg.to_string())
+    }
+}
+
+fn unsupported(what: &str) -> LpError {
+    LpError::Serde(format!("{} can't be written as line protocol", what))
+}
+
+/// Serializes the top level struct, whose members are written as fields.
+struct LineSerializer<'a> {
+    line: &'a mut String,
+    fields_start: usize,
+}
+
+impl ser::SerializeStruct for LineSerializer<'_> {
+    type Ok = ();
+    type Error = LpError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), LpError> {
+        let start = self.line.len();
+        self.line.push(if start == self.fields_start { ' ' } else { ',' });
+        escape_field_key_into(key, self.line);
+        self.line.push('=');
+        let written = value
+            .serialize(FieldSerializer { line: self.line, key })
+            .inspect_err(|_| self.line.truncate(start))?;
+        if !written {
+            // `None`, the field is left out
+            self.line.truncate(start);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), LpError> {
+        Ok(())
+    }
+}
+
+macro_rules! reject {
+    ($($method:ident($($ty:ty),*) -> $ret:ty: $what:literal;)*) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<$ret, LpError> {
+                Err(unsupported($what))
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for LineSerializer<'a> {
+    type Ok = ();
+    type Error = LpError;
+    type SerializeSeq = Impossible<(), LpError>;
+    type SerializeTuple = Impossible<(), LpError>;
+    type SerializeTupleStruct = Impossible<(), LpError>;
+    type SerializeTupleVariant = Impossible<(), LpError>;
+    type SerializeMap = Impossible<(), LpError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), LpError>;
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, LpError> {
+        Ok(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), LpError> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), LpError> {
+        Err(unsupported("an Option"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<(), LpError> {
+        Err(unsupported("an enum"))
+    }
+
+    reject! {
+        serialize_bool(bool) -> (): "a bool";
+        serialize_i8(i8) -> (): "an integer";
+        serialize_i16(i16) -> (): "an integer";
+        serialize_i32(i32) -> (): "an integer";
+        serialize_i64(i64) -> (): "an integer";
+        serialize_u8(u8) -> (): "an integer";
+        serialize_u16(u16) -> (): "an integer";
+        serialize_u32(u32) -> (): "an integer";
+        serialize_u64(u64) -> (): "an integer";
+        serialize_f32(f32) -> (): "a float";
+        serialize_f64(f64) -> (): "a float";
+        serialize_char(char) -> (): "a char";
+        serialize_str(&str) -> (): "a string";
+        serialize_bytes(&[u8]) -> (): "bytes";
+        serialize_none() -> (): "an Option";
+        serialize_unit() -> (): "()";
+        serialize_unit_struct(&'static str) -> (): "a unit struct";
+        serialize_unit_variant(&'static str, u32, &'static str) -> (): "an enum";
+        serialize_seq(Option<usize>) -> Self::SerializeSeq: "a sequence";
+        serialize_tuple(usize) -> Self::SerializeTuple: "a tuple";
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct: "a tuple struct";
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant: "an enum";
+        serialize_map(Option<usize>) -> Self::SerializeMap: "a map, only structs are supported,";
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant: "an enum";
+    }
+}
+
+/// Serializes one field value, `Ok(false)` means the field is absent.
+struct FieldSerializer<'a> {
+    line: &'a mut String,
+    key: &'static str,
+}
+
+impl FieldSerializer<'_> {
+    fn write<V: LpFieldValue + ?Sized>(self, value: &V) -> Result<bool, LpError> {
+        value.write_field(self.line, self.key)?;
+        Ok(true)
+    }
+
+    fn nested(self) -> LpError {
+        LpError::Serde(format!("field `{}` is nested, only scalar values can be written", self.key))
+    }
+}
+
+impl ser::Serializer for FieldSerializer<'_> {
+    type Ok = bool;
+    type Error = LpError;
+    type SerializeSeq = Impossible<bool, LpError>;
+    type SerializeTuple = Impossible<bool, LpError>;
+    type SerializeTupleStruct = Impossible<bool, LpError>;
+    type SerializeTupleVariant = Impossible<bool, LpError>;
+    type SerializeMap = Impossible<bool, LpError>;
+    type SerializeStruct = Impossible<bool, LpError>;
+    type SerializeStructVariant = Impossible<bool, LpError>;
+
+    fn serialize_bool(self, v: bool) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<bool, LpError> {
+        self.write(&v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<bool, LpError> {
+        self.write(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<bool, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_none(self) -> Result<bool, LpError> {
+        Ok(false)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<bool, LpError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<bool, LpError> {
+        Ok(false)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<bool, LpError> {
+        Ok(false)
+    }
+
+    /// A fieldless enum variant is written as its name.
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<bool, LpError> {
+        self.write(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<bool, LpError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        _variant: &'static str,
+                                                        _value: &T)
+                                                        -> Result<bool, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               _variant: &'static str,
+                               _len: usize)
+                               -> Result<Self::SerializeTupleVariant, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, LpError> {
+        Err(self.nested())
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                _variant: &'static str,
+                                _len: usize)
+                                -> Result<Self::SerializeStructVariant, LpError> {
+        Err(self.nested())
+    }
+}
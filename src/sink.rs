@@ -0,0 +1,139 @@
+//! Output targets the code generated by `#[derive(Influx3Lp)]` can write to.
+
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+#[cfg(feature = "std")]
+use crate::LpError;
+
+/// An output target of the code generated by `#[derive(Influx3Lp)]`, a `String`, an `IoSink`
+/// or a `LenSink`.
+///
+/// The derive generates one `__write_line` generic over `LpSink`, every public method is a thin
+/// wrapper around it, so escaping and formatting can't drift between output targets. A new
+/// target only needs an implementation of this trait.
+pub trait LpSink: fmt::Write {
+    fn push(&mut self, c: char);
+    fn push_str(&mut self, s: &str);
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl LpSink for String {
+    fn push(&mut self, c: char) {
+        String::push(self, c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        String::push_str(self, s);
+    }
+
+    fn len(&self) -> usize {
+        String::len(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized> LpSink for IoSink<'_, W> {
+    fn push(&mut self, c: char) {
+        IoSink::push(self, c);
+    }
+
+    fn push_str(&mut self, s: &str) {
+        IoSink::push_str(self, s);
+    }
+
+    fn len(&self) -> usize {
+        IoSink::len(self)
+    }
+}
+
+/// Counts the bytes of a line without storing them.
+#[derive(Default)]
+pub struct LenSink {
+    len: usize,
+}
+
+impl LpSink for LenSink {
+    fn push(&mut self, c: char) {
+        self.len += c.len_utf8();
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.len += s.len();
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl fmt::Write for LenSink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+/// Adapts an `io::Write` to the `push` / `push_str` calls of the generated code.
+///
+/// The first io error is kept and every later write is skipped.
+#[cfg(feature = "std")]
+pub struct IoSink<'a, W: ?Sized> {
+    inner: &'a mut W,
+    written: usize,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, W: io::Write + ?Sized> IoSink<'a, W> {
+    pub fn new(inner: &'a mut W) -> Self {
+        IoSink { inner, written: 0, error: None }
+    }
+
+    /// Number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.written
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.inner.write_all(s.as_bytes()) {
+            Ok(()) => self.written += s.len(),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Merge the result of the generated code with the io errors seen so far.
+    pub fn finish(self, result: Result<(), LpError>) -> io::Result<()> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        result.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized> fmt::Write for IoSink<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        match self.error {
+            Some(_) => Err(fmt::Error),
+            None => Ok(()),
+        }
+    }
+}
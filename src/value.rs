@@ -0,0 +1,854 @@
+//! How Rust values are written as field values and tag values.
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::num::{NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize};
+use core::num::{NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize};
+use core::time::Duration;
+
+use crate::escape::{escape_field_key_into, escape_field_string_into, escape_tag_key_into, escape_tag_value_into};
+use crate::{LpError, Precision, TagDecision, TagGuard};
+
+/// Maximum length in bytes of a string field value, unless `max_len` / `max_field_len` is set.
+/// InfluxDB enforces the same limit, counted in bytes.
+pub const MAX_STRING_LEN: usize = 64 * 1024;
+
+/// A type which can be written as a field value.
+///
+/// Implemented for integers (written with the `i` / `u` suffix), finite floats, `bool` and strings
+/// (quoted and escaped, newlines are rejected). Implement it to use your own types as fields, `field` is the field
+/// key to report in errors:
+///
+/// ```rust
+/// use std::fmt::Write;
+/// use influx3_lp::{Influx3Lp, LpError, LpFieldValue};
+///
+/// enum Level { Low, High }
+///
+/// impl LpFieldValue for Level {
+///     fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+///         let level = match self {
+///             Level::Low => "low",
+///             Level::High => "high",
+///         };
+///         level.write_field(w, field)
+///     }
+/// }
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "alarm")]
+/// struct Alarm {
+///     pub level: Level,
+/// }
+///
+/// assert_eq!(Alarm { level: Level::High }.to_lp(), "alarm level=\"high\"");
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be a field value",
+    label = "not a number, bool, string or other type implementing `LpFieldValue`",
+    note = "mark the member #[influx3_lp(flatten)] if its type derives `Influx3Lp`, #[influx3_lp(skip)] to leave it out, or implement `LpFieldValue` for its type"
+)]
+pub trait LpFieldValue {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+macro_rules! impl_field_value {
+    ($suffix:literal: $($ty:ty),*) => {
+        $(
+            impl LpFieldValue for $ty {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, _field: &'static str) -> Result<(), LpError> {
+                    let _ = write!(w, concat!("{}", $suffix), self);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_field_value!("i": i8, i16, i32, i64, isize);
+impl_field_value!("u": u8, u16, u32, u64, usize);
+impl_field_value!("": bool);
+
+macro_rules! impl_non_zero_field_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LpFieldValue for $ty {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    self.get().write_field(w, field)
+                }
+            }
+        )*
+    };
+}
+
+impl_non_zero_field_value!(NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize);
+impl_non_zero_field_value!(NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize);
+
+macro_rules! impl_wide_field_value {
+    ($suffix:literal: $($ty:ty => $narrow:ty),*) => {
+        $(
+            impl LpFieldValue for $ty {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    // InfluxDB integers are 64 bit
+                    let Ok(v) = <$narrow>::try_from(*self) else {
+                        return Err(LpError::IntegerOutOfRange { field: field.to_string() });
+                    };
+                    let _ = write!(w, concat!("{}", $suffix), v);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_wide_field_value!("i": i128 => i64);
+impl_wide_field_value!("u": u128 => u64);
+
+macro_rules! impl_float_field_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LpFieldValue for $ty {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    // line protocol has no NaN or infinity
+                    if !self.is_finite() {
+                        return Err(LpError::InvalidFloat { field: field.to_string() });
+                    }
+                    // Display never uses an exponent, e.g. 1e20 is written in full
+                    let _ = write!(w, "{}", self);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_float_field_value!(f32, f64);
+
+/// Floats which can be written with a fixed number of decimal places.
+pub trait LpFloat {
+    fn to_f64(&self) -> f64;
+}
+
+impl LpFloat for f32 {
+    fn to_f64(&self) -> f64 {
+        f64::from(*self)
+    }
+}
+
+impl LpFloat for f64 {
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+/// Write `v` rounded to `precision` decimal places, trailing zeros are kept.
+pub fn write_float_with_precision<F, W>(v: &F, w: &mut W, field: &'static str, precision: usize) -> Result<(), LpError>
+where
+    F: LpFloat + ?Sized,
+    W: Write + ?Sized,
+{
+    let v = v.to_f64();
+    if !v.is_finite() {
+        return Err(LpError::InvalidFloat { field: field.to_string() });
+    }
+    let _ = write!(w, "{:.*}", precision, v);
+    Ok(())
+}
+
+/// Write `v` like a plain float field, with `.0` appended when it's integral.
+pub fn write_float_explicit<F, W>(v: &F, w: &mut W, field: &'static str) -> Result<(), LpError>
+where
+    F: LpFloat + LpFieldValue + ?Sized,
+    W: Write + ?Sized,
+{
+    v.write_field(w, field)?;
+    // finite here, and Display writes integral floats without a decimal point
+    if v.to_f64() % 1.0 == 0.0 {
+        let _ = w.write_str(".0");
+    }
+    Ok(())
+}
+
+/// Write `v` as an `i` integer counting `unit` nanoseconds, the remainder is dropped.
+pub fn write_duration<W: Write + ?Sized>(v: &Duration, w: &mut W, field: &'static str, unit: u128) -> Result<(), LpError> {
+    let count = v.as_nanos() / unit;
+    let count = i64::try_from(count).map_err(|_| LpError::IntegerOutOfRange { field: field.to_string() })?;
+    count.write_field(w, field)
+}
+
+/// Numbers which can be written with another line protocol type, see `#[influx3_lp(as = "...")]`.
+pub trait LpNumber {
+    /// Write the number as a float, without type suffix.
+    fn write_as_float<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+    /// Write the number as an `i` integer, it must be integral and fit in `i64`.
+    fn write_as_integer<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+macro_rules! impl_integer_number {
+    ($($ty:ty),*) => {
+        $(
+            impl LpNumber for $ty {
+                fn write_as_float<W: Write + ?Sized>(&self, w: &mut W, _field: &'static str) -> Result<(), LpError> {
+                    // integer digits are a valid float, and exact even beyond 2^53
+                    let _ = write!(w, "{}", self);
+                    Ok(())
+                }
+
+                fn write_as_integer<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    let v = i64::try_from(*self).map_err(|_| LpError::IntegerOutOfRange { field: field.to_string() })?;
+                    v.write_field(w, field)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_number!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_float_number {
+    ($($ty:ty),*) => {
+        $(
+            impl LpNumber for $ty {
+                fn write_as_float<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    self.write_field(w, field)
+                }
+
+                fn write_as_integer<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    let v = self.to_f64();
+                    if !v.is_finite() {
+                        return Err(LpError::NotAnInteger { field: field.to_string() });
+                    }
+                    // -2^63 is exact, 2^63 is the first float above i64::MAX
+                    if !(-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&v) {
+                        return Err(LpError::IntegerOutOfRange { field: field.to_string() });
+                    }
+                    // the cast drops the fractional part
+                    let i = v as i64;
+                    if i as f64 != v {
+                        return Err(LpError::NotAnInteger { field: field.to_string() });
+                    }
+                    i.write_field(w, field)
+                }
+            }
+        )*
+    };
+}
+
+impl_float_number!(f32, f64);
+
+/// A measurement taken from a member at runtime gets the checks a literal `table_name`
+/// gets at compile time.
+pub fn check_measurement(name: &str) -> Result<(), LpError> {
+    if name.is_empty() || name.starts_with('_') || name.contains(['\n', '\r']) {
+        return Err(LpError::InvalidMeasurement { name: name.to_string() });
+    }
+    Ok(())
+}
+
+/// Names InfluxDB reserves for the timestamp column, mirroring the checks of the derive.
+const RESERVED_KEYS: &[&str] = &["time", "_time"];
+
+/// A tag or field key given at runtime gets the checks the derive makes at compile time, keys
+/// can't be empty or reserved, nor hold a newline which has no escape sequence.
+pub fn check_key(key: &str) -> Result<(), LpError> {
+    if key.is_empty() || RESERVED_KEYS.contains(&key) || key.contains(['\n', '\r']) {
+        return Err(LpError::InvalidKey { key: key.to_string() });
+    }
+    Ok(())
+}
+
+/// Compile time counterpart of `check_measurement`, for a `table_name` given by a const.
+pub const fn assert_measurement(name: &str) {
+    let bytes = name.as_bytes();
+    assert!(!bytes.is_empty(), "table_name must not be empty");
+    assert!(bytes[0] != b'_', "table_name must not start with `_`, which InfluxDB reserves for system tables");
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i] != b'\n' && bytes[i] != b'\r', "table_name must not hold a newline");
+        i += 1;
+    }
+}
+
+/// Tag values can't hold a newline, there is no escape sequence for it.
+pub fn check_tag_value(key: &str, value: &str) -> Result<(), LpError> {
+    if value.contains(['\n', '\r']) {
+        return Err(LpError::InvalidTagValue { tag: key.to_string() });
+    }
+    Ok(())
+}
+
+/// Strip the leading and trailing whitespace of a tag value, borrowing when nothing is stripped.
+pub fn trim_tag_value(value: Cow<'_, str>) -> Cow<'_, str> {
+    match value {
+        Cow::Borrowed(s) => Cow::Borrowed(s.trim()),
+        Cow::Owned(s) if s.trim().len() == s.len() => Cow::Owned(s),
+        Cow::Owned(s) => Cow::Owned(s.trim().to_string()),
+    }
+}
+
+/// A type which can be the timestamp of a line, an integer or a `SystemTime`.
+///
+/// Any other type given to `#[influx3_lp(timestamp)]` is a compile error, rather than a malformed
+/// line at runtime.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be a timestamp",
+    label = "not an integer or `SystemTime`",
+    note = "a timestamp must be an integer or `std::time::SystemTime`, optionally in an `Option`"
+)]
+pub trait LpTimestamp {
+    /// Write the space before the timestamp and the timestamp, `precision` is its declared unit.
+    fn write_timestamp<W: Write + ?Sized>(&self, w: &mut W, precision: Precision) -> Result<(), LpError>;
+}
+
+impl<T: LpTimestamp + ?Sized> LpTimestamp for &T {
+    fn write_timestamp<W: Write + ?Sized>(&self, w: &mut W, precision: Precision) -> Result<(), LpError> {
+        (**self).write_timestamp(w, precision)
+    }
+}
+
+macro_rules! impl_integer_timestamp {
+    ($($ty:ty),*) => {
+        $(
+            /// Written as it is, already in the declared unit.
+            impl LpTimestamp for $ty {
+                fn write_timestamp<W: Write + ?Sized>(&self, w: &mut W, _precision: Precision) -> Result<(), LpError> {
+                    let _ = write!(w, " {}", self);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_timestamp!(i8, i16, i32, i64, u8, u16, u32);
+
+macro_rules! impl_wide_integer_timestamp {
+    ($($ty:ty),*) => {
+        $(
+            /// Written as it is, already in the declared unit, if it fits in the `i64` of InfluxDB.
+            impl LpTimestamp for $ty {
+                fn write_timestamp<W: Write + ?Sized>(&self, w: &mut W, _precision: Precision) -> Result<(), LpError> {
+                    let units = i64::try_from(*self).map_err(|_| LpError::InvalidTimestamp)?;
+                    let _ = write!(w, " {}", units);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_wide_integer_timestamp!(i128, isize, u64, u128, usize);
+
+/// Units of the precision since the Unix epoch, truncated.
+#[cfg(feature = "std")]
+impl LpTimestamp for std::time::SystemTime {
+    fn write_timestamp<W: Write + ?Sized>(&self, w: &mut W, precision: Precision) -> Result<(), LpError> {
+        let units = self
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .and_then(|d| i64::try_from(d.as_nanos() / precision.nanos()).ok())
+            .ok_or(LpError::InvalidTimestamp)?;
+        let _ = write!(w, " {}", units);
+        Ok(())
+    }
+}
+
+/// Generated code calls `(&MapOrder(map)).sorted()` to decide whether the entries of a map are
+/// sorted by key. Method lookup tries `InsertionOrder` on `MapOrder` first, which only an
+/// `IndexMap` implements, and falls back to `AnyOrder` on `&MapOrder`, which sorts.
+pub struct MapOrder<'a, M: ?Sized>(pub &'a M);
+
+/// Declared without the `indexmap` feature too, so generated code can import it whatever the features.
+pub trait InsertionOrder {
+    fn sorted(&self) -> bool;
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> InsertionOrder for MapOrder<'_, indexmap::IndexMap<K, V, S>> {
+    fn sorted(&self) -> bool {
+        false
+    }
+}
+
+pub trait AnyOrder {
+    fn sorted(&self) -> bool;
+}
+
+impl<M: ?Sized> AnyOrder for &MapOrder<'_, M> {
+    fn sorted(&self) -> bool {
+        true
+    }
+}
+
+/// Write the entries of a map as fields, sorted by key if `sorted`, `first` tells whether a
+/// field was written before. `write_value` writes a value, in the `FieldStyle` of the struct.
+pub fn write_field_map<'a, K, V, W>(
+    entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+    w: &mut W,
+    first: bool,
+    sorted: bool,
+    write_value: impl Fn(&V, &mut W) -> Result<(), LpError>,
+) -> Result<(), LpError>
+where
+    K: AsRef<str> + ?Sized + 'a,
+    V: ?Sized + 'a,
+    W: Write + ?Sized,
+{
+    let mut entries: Vec<_> = entries.into_iter().map(|(k, v)| (k.as_ref(), v)).collect();
+    if sorted {
+        entries.sort_by_key(|(k, _)| *k);
+    }
+    for (i, (key, value)) in entries.into_iter().enumerate() {
+        let _ = w.write_char(if first && i == 0 { ' ' } else { ',' });
+        escape_field_key_into(key, w);
+        let _ = w.write_char('=');
+        write_value(value, w).map_err(|e| e.with_field(key))?;
+    }
+    Ok(())
+}
+
+/// Write the entries of a map as tags, sorted by key if `sorted`, an entry with an empty value
+/// is left out.
+pub fn write_tag_map<'a, K, V, W>(
+    entries: impl IntoIterator<Item = (&'a K, &'a V)>,
+    w: &mut W,
+    guard: &dyn TagGuard,
+    sorted: bool,
+) -> Result<(), LpError>
+where
+    K: AsRef<str> + ?Sized + 'a,
+    V: LpTagValue + ?Sized + 'a,
+    W: Write + ?Sized,
+{
+    let mut entries: Vec<_> = entries.into_iter().map(|(k, v)| (k.as_ref(), v.tag_value())).collect();
+    if sorted {
+        entries.sort_by_key(|(k, _)| *k);
+    }
+    for (key, value) in entries {
+        let value = match guard.check(key, &value) {
+            TagDecision::Keep => value,
+            TagDecision::Drop => continue,
+            TagDecision::Replace(v) => Cow::Owned(v),
+        };
+        if value.is_empty() {
+            continue;
+        }
+        check_tag_value(key, &value)?;
+        let _ = w.write_char(',');
+        escape_tag_key_into(key, w);
+        let _ = w.write_char('=');
+        escape_tag_value_into(&value, w);
+    }
+    Ok(())
+}
+
+/// Write the escaped `,key=value` tags of `tags` followed by the `extra` tags given at the call
+/// site, sorted by key when `sorted`. A key written twice is an error, wherever it comes from: a
+/// tag member, a flattened member, a tags map or `extra`.
+pub fn write_merged_tags<W: Write + ?Sized>(
+    tags: &str,
+    extra: &[(&str, &str)],
+    sorted: bool,
+    w: &mut W,
+) -> Result<(), LpError> {
+    let mut entries = split_tags(tags);
+    for (key, value) in extra.iter().filter(|(_, value)| !value.is_empty()) {
+        check_tag_value(key, value)?;
+        let mut entry = String::with_capacity(key.len() + value.len() + 1);
+        escape_tag_key_into(key, &mut entry);
+        entry.push('=');
+        escape_tag_value_into(value, &mut entry);
+        entries.push((key.to_string(), Cow::Owned(entry)));
+    }
+    for (i, (key, _)) in entries.iter().enumerate() {
+        if entries[..i].iter().any(|(k, _)| k == key) {
+            return Err(LpError::DuplicateTagKey { tag: key.to_string() });
+        }
+    }
+    if sorted {
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+    for (_, entry) in entries {
+        let _ = w.write_char(',');
+        let _ = w.write_str(&entry);
+    }
+    Ok(())
+}
+
+/// Write the escaped fields of `fields`, starting with their ` ` separator, unless a key is
+/// written twice, e.g. by a member and a flattened member or a fields map.
+pub fn write_unique_fields<W: Write + ?Sized>(fields: &str, w: &mut W) -> Result<(), LpError> {
+    let keys = field_keys(fields);
+    for (i, key) in keys.iter().enumerate() {
+        if keys[..i].contains(key) {
+            return Err(LpError::DuplicateFieldKey { field: key.clone() });
+        }
+    }
+    let _ = w.write_str(fields);
+    Ok(())
+}
+
+/// The unescaped keys of escaped ` key=value,key=value` fields, a comma inside a quoted string
+/// value doesn't end a field.
+fn field_keys(fields: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut in_key = true;
+    let mut in_string = false;
+    let mut chars = fields.chars().skip(1);
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_key => key.extend(chars.next()),
+            '\\' => {
+                chars.next();
+            }
+            '=' if in_key => {
+                keys.push(core::mem::take(&mut key));
+                in_key = false;
+            }
+            '"' if !in_key => in_string = !in_string,
+            ',' if !in_key && !in_string => in_key = true,
+            c if in_key => key.push(c),
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// Split escaped `,key=value` tags into their unescaped keys and escaped `key=value` entries.
+fn split_tags(tags: &str) -> Vec<(String, Cow<'_, str>)> {
+    let mut entries = Vec::new();
+    let bytes = tags.as_bytes();
+    // every special char is escaped, a backslash included, so the byte after one is skipped
+    let mut start = 1;
+    let mut i = 1;
+    while i <= bytes.len() {
+        if i == bytes.len() || bytes[i] == b',' {
+            let entry = &tags[start..i];
+            entries.push((unescape_tag_key(entry), Cow::Borrowed(entry)));
+            start = i + 1;
+        } else if bytes[i] == b'\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    entries
+}
+
+/// The unescaped key of an escaped `key=value` entry, ended by the first `=` which isn't escaped.
+fn unescape_tag_key(entry: &str) -> String {
+    let mut key = String::new();
+    let mut chars = entry.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '=' => break,
+            '\\' => key.extend(chars.next()),
+            c => key.push(c),
+        }
+    }
+    key
+}
+
+/// A type which can be written as a tag value, unescaped.
+///
+/// Implemented for strings (borrowed as they are), integers, floats, `bool` and `char`. Implement
+/// it to use your own types as tags, e.g. an enum whose variants stand for a fixed set of values:
+///
+/// ```rust
+/// use std::borrow::Cow;
+/// use influx3_lp::{Influx3Lp, LpTagValue};
+///
+/// enum Severity { Low, High }
+///
+/// impl LpTagValue for Severity {
+///     fn tag_value(&self) -> Cow<'_, str> {
+///         match self {
+///             Severity::Low => Cow::Borrowed("low"),
+///             Severity::High => Cow::Borrowed("high"),
+///         }
+///     }
+/// }
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "alarm")]
+/// struct Alarm {
+///     pub code: u32,
+///     #[influx3_lp(tag)]
+///     pub severity: Severity,
+/// }
+///
+/// assert_eq!(Alarm { code: 7, severity: Severity::High }.to_lp(), "alarm,severity=high code=7u");
+/// ```
+///
+/// A type implementing `Display` can return `Cow::Owned(self.to_string())`. A foreign type, which
+/// can't implement this trait here, is written with its `Display` by `#[influx3_lp(tag, display)]`.
+pub trait LpTagValue {
+    fn tag_value(&self) -> Cow<'_, str>;
+}
+
+macro_rules! impl_pointer_tag_value {
+    ($($ptr:ty),*) => {
+        $(
+            impl<T: LpTagValue + ?Sized> LpTagValue for $ptr {
+                fn tag_value(&self) -> Cow<'_, str> {
+                    (**self).tag_value()
+                }
+            }
+        )*
+    };
+}
+
+impl_pointer_tag_value!(&T, Arc<T>, Rc<T>);
+
+macro_rules! impl_str_tag_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LpTagValue for $ty {
+                fn tag_value(&self) -> Cow<'_, str> {
+                    Cow::Borrowed(self.as_ref())
+                }
+            }
+        )*
+    };
+}
+
+impl_str_tag_value!(str, String, Cow<'_, str>, Box<str>);
+
+macro_rules! impl_display_tag_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LpTagValue for $ty {
+                fn tag_value(&self) -> Cow<'_, str> {
+                    Cow::Owned(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_display_tag_value!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_display_tag_value!(f32, f64, bool, char);
+impl_display_tag_value!(IpAddr, Ipv4Addr, Ipv6Addr);
+
+/// How the plain fields of a struct are written, from `bool_format` and `unsigned`.
+#[derive(Clone, Copy)]
+pub struct FieldStyle {
+    /// `t` / `f` instead of `true` / `false`
+    pub short_bool: bool,
+    /// unsigned integers as `i` integers
+    pub signed: bool,
+}
+
+/// Wraps a field value with its `FieldStyle`.
+///
+/// Generated code calls `(&Styled(v, style)).write_styled(buf, field)`. Method lookup tries
+/// `StyledBool` and `StyledUnsigned` on `Styled` first, and only falls back to `StyledAny` on
+/// `&Styled`, which ignores the style, when `v` is neither a `bool` nor an unsigned integer.
+pub struct Styled<'a, T: ?Sized>(pub &'a T, pub FieldStyle);
+
+pub trait StyledBool {
+    fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+impl StyledBool for Styled<'_, bool> {
+    fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        if !self.1.short_bool {
+            return self.0.write_field(w, field);
+        }
+        let _ = w.write_char(if *self.0 { 't' } else { 'f' });
+        Ok(())
+    }
+}
+
+pub trait StyledUnsigned {
+    fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+macro_rules! impl_styled_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl StyledUnsigned for Styled<'_, $ty> {
+                fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    if self.1.signed {
+                        self.0.write_as_integer(w, field)
+                    } else {
+                        self.0.write_field(w, field)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_styled_unsigned!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_styled_non_zero {
+    ($($ty:ty),*) => {
+        $(
+            impl StyledUnsigned for Styled<'_, $ty> {
+                fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    Styled(&self.0.get(), self.1).write_styled(w, field)
+                }
+            }
+        )*
+    };
+}
+
+impl_styled_non_zero!(NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize);
+
+pub trait StyledAny {
+    fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+impl<T: LpFieldValue + ?Sized> StyledAny for &Styled<'_, T> {
+    fn write_styled<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        self.0.write_field(w, field)
+    }
+}
+
+impl LpFieldValue for str {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        write_str_field(self, w, field, StrLimit { max_len: MAX_STRING_LEN, chars: false, truncate: false })
+    }
+}
+
+/// Write a quoted string field value within `limit`.
+fn write_str_field<W: Write + ?Sized>(s: &str, w: &mut W, field: &'static str, limit: StrLimit) -> Result<(), LpError> {
+    // a string has at most as many chars as bytes, so most strings are never counted
+    let mut s = s;
+    if s.len() > limit.max_len && (!limit.chars || s.chars().count() > limit.max_len) {
+        if !limit.truncate {
+            return Err(LpError::FieldTooLong { field: field.to_string() });
+        }
+        // cut before escaping, so no escape sequence is split
+        let end = if limit.chars {
+            s.char_indices().nth(limit.max_len).map_or(s.len(), |(i, _)| i)
+        } else {
+            (0..=limit.max_len).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0)
+        };
+        s = &s[..end];
+    }
+    // a newline would end the line, and it has no escape sequence
+    if s.contains(['\n', '\r']) {
+        return Err(LpError::InvalidFieldValue { field: field.to_string() });
+    }
+    // string field value should be qutoed
+    let _ = w.write_char('"');
+    escape_field_string_into(s, w);
+    let _ = w.write_char('"');
+    Ok(())
+}
+
+/// The length limit of a string field from `max_len` / `max_field_len`, `len_unit` and `on_overflow`.
+#[derive(Clone, Copy)]
+pub struct StrLimit {
+    pub max_len: usize,
+    /// count chars instead of bytes
+    pub chars: bool,
+    /// cut an over-long value instead of failing
+    pub truncate: bool,
+}
+
+/// Wraps a field value with its `StrLimit`.
+///
+/// Generated code calls `(&MaxLen(v, limit)).write_limited(buf, field)`. Method lookup tries
+/// `LimitedStr` on `MaxLen` first, and only falls back to `LimitedAny` on `&MaxLen`, which
+/// ignores the limit, when `v` isn't a string. `is_limited()` tells the two apart, so generated
+/// code can apply a `FieldStyle` to the other types instead.
+pub struct MaxLen<'a, T: ?Sized>(pub &'a T, pub StrLimit);
+
+pub trait LimitedStr {
+    fn is_limited(&self) -> bool {
+        true
+    }
+
+    fn write_limited<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+macro_rules! impl_limited_str {
+    ($($ty:ty),*) => {
+        $(
+            impl LimitedStr for MaxLen<'_, $ty> {
+                fn write_limited<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    write_str_field(self.0.as_ref(), w, field, self.1)
+                }
+            }
+        )*
+    };
+}
+
+impl_limited_str!(str, &str, String, Cow<'_, str>, Box<str>, Arc<str>, Rc<str>, Arc<String>, Rc<String>);
+
+pub trait LimitedAny {
+    fn is_limited(&self) -> bool {
+        false
+    }
+
+    fn write_limited<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError>;
+}
+
+impl<T: LpFieldValue + ?Sized> LimitedAny for &MaxLen<'_, T> {
+    fn write_limited<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        self.0.write_field(w, field)
+    }
+}
+
+impl LpFieldValue for char {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        self.encode_utf8(&mut [0; 4]).write_field(w, field)
+    }
+}
+
+macro_rules! impl_ip_field_value {
+    ($($ty:ty),*) => {
+        $(
+            impl LpFieldValue for $ty {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, _field: &'static str) -> Result<(), LpError> {
+                    // digits, dots, colons and hex letters, nothing to escape
+                    let _ = write!(w, "\"{}\"", self);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_ip_field_value!(IpAddr, Ipv4Addr, Ipv6Addr);
+
+impl LpFieldValue for String {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        self.as_str().write_field(w, field)
+    }
+}
+
+impl LpFieldValue for Cow<'_, str> {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        self.as_ref().write_field(w, field)
+    }
+}
+
+impl<T: LpFieldValue + ?Sized> LpFieldValue for &T {
+    fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+        (**self).write_field(w, field)
+    }
+}
+
+macro_rules! impl_pointer_field_value {
+    ($($ptr:ident),*) => {
+        $(
+            impl<T: LpFieldValue + ?Sized> LpFieldValue for $ptr<T> {
+                fn write_field<W: Write + ?Sized>(&self, w: &mut W, field: &'static str) -> Result<(), LpError> {
+                    (**self).write_field(w, field)
+                }
+            }
+        )*
+    };
+}
+
+impl_pointer_field_value!(Box, Arc, Rc);
@@ -0,0 +1,102 @@
+//! Length-prefixed framing of line protocol records for a local write-ahead log.
+//!
+//! Every record is the little-endian `u32` byte length of the line followed by the line itself.
+//! A record cut short by a crash is detected while reading and treated as the end of the log,
+//! so everything before it can be recovered and forwarded to InfluxDB.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io::{self, Read, Write};
+
+use crate::Influx3Lp;
+
+/// Serialize `item` and append it to `w` as one framed record.
+///
+/// An invalid value is reported as an `InvalidData` error, and nothing is written.
+pub fn append_wal<T: Influx3Lp, W: Write>(item: &T, w: &mut W) -> io::Result<()> {
+    let line = item.try_to_lp().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let len = u32::try_from(line.len()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "line is too long for a WAL record")
+    })?;
+
+    // one write per record, so a torn write can only lose the last record
+    let mut frame = Vec::with_capacity(4 + line.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(line.as_bytes());
+    w.write_all(&frame)
+}
+
+/// Iterator over the lines stored in a WAL written by `append_wal`.
+///
+/// Iteration stops at the end of the log or at a truncated trailing record.
+/// A record which is not valid UTF-8 is reported as an `InvalidData` error.
+pub struct WalReader<R> {
+    inner: R,
+    done: bool,
+}
+
+impl<R: Read> WalReader<R> {
+    /// Read records from `inner`, starting at its current position.
+    pub fn new(inner: R) -> Self {
+        WalReader { inner, done: false }
+    }
+
+    /// Give back the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read as many bytes as possible into `buf`, returning how many were read.
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(read)
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<String>> {
+        let mut len = [0u8; 4];
+        if self.fill(&mut len)? < len.len() {
+            return Ok(None);
+        }
+
+        // the length of a torn or corrupted header can't be trusted with an allocation,
+        // the buffer only grows with the bytes actually read
+        let len = u32::from_le_bytes(len) as usize;
+        let mut line = Vec::new();
+        if (&mut self.inner).take(len as u64).read_to_end(&mut line)? < len {
+            return Ok(None);
+        }
+
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<R: Read> Iterator for WalReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some(line)) => Some(Ok(line)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
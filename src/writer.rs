@@ -0,0 +1,88 @@
+//! Size-bounded batching of points into line protocol bodies.
+
+use alloc::string::String;
+use std::io::{self, Write};
+
+use crate::{__private, Influx3Lp};
+
+/// Buffers points and writes them to `inner` in batches of at most `max_body_bytes`.
+///
+/// Every line of a batch ends with `\n`, and each batch is written with a single `write_all`,
+/// so a writer sending one request per write gets complete bodies. A point is added to the
+/// current batch unless it would take it over the limit, the batch is then written first.
+/// A single line over the limit is written as a batch of its own.
+///
+/// Buffered lines are only written by `push`, `flush` or `into_inner`, call `flush` before
+/// dropping the writer or they are lost.
+///
+/// ```rust
+/// use influx3_lp::{Influx3Lp, LpWriter};
+///
+/// #[derive(Influx3Lp)]
+/// #[influx3_lp(table_name = "home")]
+/// struct SensorData {
+///     pub temp: f32,
+/// }
+///
+/// let mut writer = LpWriter::new(Vec::new(), 1024);
+/// writer.push(&SensorData { temp: 21.0 }).unwrap();
+/// writer.push(&SensorData { temp: 22.5 }).unwrap();
+/// assert_eq!(writer.into_inner().unwrap(), b"home temp=21\nhome temp=22.5\n");
+/// ```
+pub struct LpWriter<W: Write> {
+    inner: W,
+    buf: String,
+    max_body_bytes: usize,
+}
+
+impl<W: Write> LpWriter<W> {
+    pub fn new(inner: W, max_body_bytes: usize) -> Self {
+        LpWriter { inner, buf: String::new(), max_body_bytes }
+    }
+
+    /// Add a point to the current batch, writing the batch when it's full.
+    ///
+    /// An invalid value is reported as an `InvalidData` error and the point is left out, the
+    /// batch is kept.
+    pub fn push<T: Influx3Lp>(&mut self, point: &T) -> io::Result<()> {
+        let start = self.buf.len();
+        point.__write_lp(&mut self.buf, &__private::Context::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.buf.push('\n');
+
+        // the new line doesn't fit, it starts the next batch
+        if start > 0 && self.buf.len() > self.max_body_bytes {
+            self.inner.write_all(&self.buf.as_bytes()[..start])?;
+            self.buf.drain(..start);
+        }
+        if self.buf.len() >= self.max_body_bytes {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Write the current batch, even if it's not full, and flush `inner`.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_batch()?;
+        self.inner.flush()
+    }
+
+    /// Flush and give back the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Number of bytes buffered in the current batch.
+    pub fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(self.buf.as_bytes())?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
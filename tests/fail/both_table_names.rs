@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    #[influx3_lp(table_name)]
+    pub table: String,
+    pub temp: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "meter")]
+struct Meter {
+    #[influx3_lp(as = "float")]
+    pub reading: String,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "meter")]
+struct Meter {
+    pub reading: i64,
+    #[influx3_lp(tag, as = "integer")]
+    pub device: u32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home", coalesce(key = "note", primary, fallback))]
+struct SensorData {
+    #[influx3_lp(max_len = 16)]
+    pub primary: Option<String>,
+    pub fallback: Option<String>,
+}
+
+fn main() {}
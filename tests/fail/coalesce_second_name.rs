@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home", coalesce(primary, fallback))]
+struct SensorData {
+    pub primary: Option<f32>,
+    #[influx3_lp(name = "backup", unit = "celsius")]
+    pub fallback: Option<f32>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home", rename_all = "camelCase")]
+struct SensorData {
+    pub room_temp: f32,
+    #[influx3_lp(name = "roomTemp")]
+    pub temp: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+#[influx3_lp(table_name = "office")]
+struct SensorData {
+    pub temp: f32,
+}
+
+fn main() {}
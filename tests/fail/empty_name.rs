@@ -0,0 +1,10 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    #[influx3_lp(field, name = "")]
+    pub temperature_celsius: f32,
+}
+
+fn main() {}
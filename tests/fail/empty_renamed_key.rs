@@ -0,0 +1,10 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home", rename_all = "camelCase")]
+struct SensorData {
+    pub temp: f32,
+    pub __: f32,
+}
+
+fn main() {}
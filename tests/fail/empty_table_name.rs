@@ -0,0 +1,9 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "")]
+struct SensorData {
+    pub temp: f32,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "event")]
+enum Event {
+    Door { is_open: bool },
+    Reboot(u32),
+}
+
+fn main() {}
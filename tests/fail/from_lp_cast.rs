@@ -0,0 +1,10 @@
+use influx3_lp::{FromLp, Influx3Lp};
+
+#[derive(Influx3Lp, FromLp)]
+#[influx3_lp(table_name = "meter")]
+struct Meter {
+    #[influx3_lp(as = "float")]
+    pub reading: i64,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+struct SensorData {
+    pub temp: f32,
+}
+
+fn main() {}
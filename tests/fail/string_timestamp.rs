@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    pub temp: f32,
+    #[influx3_lp(timestamp)]
+    pub timestamp: String,
+}
+
+fn main() {}
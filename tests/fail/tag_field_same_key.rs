@@ -0,0 +1,12 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    #[influx3_lp(tag)]
+    pub room: String,
+    #[influx3_lp(name = "room")]
+    pub room_name: String,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "point")]
+struct Point(f64, f64);
+
+fn main() {}
@@ -0,0 +1,11 @@
+use influx3_lp::Influx3Lp;
+
+const INTERNAL: &str = "_internal";
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = INTERNAL)]
+struct SensorData {
+    pub temp: f32,
+}
+
+fn main() {}
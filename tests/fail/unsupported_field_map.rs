@@ -0,0 +1,12 @@
+use std::collections::HashMap;
+
+use influx3_lp::Influx3Lp;
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    pub temp: f64,
+    pub extra: HashMap<String, f64>,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use influx3_lp::Influx3Lp;
+
+struct Location {
+    lat: f64,
+    lon: f64,
+}
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    pub temp: f64,
+    pub location: Option<Location>,
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use influx3_lp::Influx3Lp;
+
+struct Reading {
+    value: f64,
+}
+
+#[derive(Influx3Lp)]
+#[influx3_lp(table_name = "home")]
+struct SensorData {
+    pub temp: f64,
+    pub readings: Vec<Reading>,
+}
+
+fn main() {}
@@ -79,6 +79,87 @@ fn test_has_at_least_one_field() {
     t.compile_fail("tests/fail/field_count.rs");
 }
 
+#[test]
+fn test_only_skipped_field() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/only_skipped_field.rs");
+}
+
+#[test]
+fn test_reserved_names() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/reserved_field_key.rs");
+    t.compile_fail("tests/fail/reserved_tag_key.rs");
+    t.compile_fail("tests/fail/empty_renamed_key.rs");
+    t.compile_fail("tests/fail/reserved_table_name.rs");
+}
+
+#[test]
+fn test_invalid_table_name() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/empty_table_name.rs");
+    t.compile_fail("tests/fail/underscore_table_name.rs");
+    t.compile_fail("tests/fail/underscore_table_name_const.rs");
+    t.compile_fail("tests/fail/missing_table_name.rs");
+    t.compile_fail("tests/fail/both_table_names.rs");
+    t.compile_fail("tests/fail/duplicate_table_name.rs");
+}
+
+#[test]
+fn test_invalid_cast() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/cast_string.rs");
+    t.compile_fail("tests/fail/cast_tag.rs");
+}
+
+#[test]
+fn test_from_lp_unreadable() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/from_lp_cast.rs");
+}
+
+#[test]
+fn test_coalesce_member_attributes() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/coalesce_max_len.rs");
+    t.compile_fail("tests/fail/coalesce_second_name.rs");
+}
+
+#[test]
+fn test_empty_name() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/empty_name.rs");
+}
+
+#[test]
+fn test_duplicate_key() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/duplicate_key.rs");
+    // tags and fields share one namespace of keys
+    t.compile_fail("tests/fail/tag_field_same_key.rs");
+}
+
+#[test]
+fn test_conflicting_roles() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/tag_and_timestamp.rs");
+    t.compile_fail("tests/fail/two_timestamps.rs");
+}
+
+// the types listed in the error include `SystemTime` only with the `std` feature
+#[cfg(feature = "std")]
+#[test]
+fn test_invalid_timestamp_type() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/string_timestamp.rs");
+}
+
+#[test]
+fn test_enum_tuple_variant() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/enum_tuple_variant.rs");
+}
+
 #[test]
 fn test_string_field() {
     #[derive(Influx3Lp)]
@@ -87,7 +168,7 @@ fn test_string_field() {
         pub temp: f32,
         pub hum: f64,
         pub date: String,
-        pub time: String,
+        pub clock: String,
         #[influx3_lp(tag)]
         pub room: String,
         #[influx3_lp(tag)]
@@ -98,14 +179,14 @@ fn test_string_field() {
         temp: 21.0,
         hum: 35.9,
         date: String::from("2025 09 18"),
-        time: String::from("12/12/12"),
+        clock: String::from("12/12/12"),
         room: String::from("Kitchen"),
         city: String::from("New York"),
     };
 
     let serialized = data.to_lp();
     assert_eq!(serialized, 
-               "home,room=Kitchen,city=New\\ York temp=21,hum=35.9,date=\"2025 09 18\",time=\"12/12/12\"");
+               "home,room=Kitchen,city=New\\ York temp=21,hum=35.9,date=\"2025 09 18\",clock=\"12/12/12\"");
 }
 
 #[test]
@@ -195,7 +276,7 @@ fn test_special_charactors() {
     };
 
     let serialized = data.to_lp();
-    let expected = format!("special\\ data,t1=with\\,comma,t2=with\\ equal\\ \\=,t3=\\ with\\ space\\  f1=\"with,comma\",f2=\"with equal =\",f3=\" with space \",f4=\"double quote \\\" and back slash\\\\\"");
+    let expected = String::from("special\\ data,t1=with\\,comma,t2=with\\ equal\\ \\=,t3=\\ with\\ space\\  f1=\"with,comma\",f2=\"with equal =\",f3=\" with space \",f4=\"double quote \\\" and back slash\\\\\"");
     println!("{}", expected);
     assert_eq!(serialized, expected);    
 }
@@ -323,3 +404,3010 @@ fn test_optional_timestamp() {
                "home temp=21,hum=35.9");
 }
 
+
+#[test]
+fn test_wide_integer_timestamp() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "ts")]
+    struct Unsigned {
+        pub v: i64,
+        #[influx3_lp(timestamp)]
+        pub timestamp: u64,
+    }
+
+    let data = Unsigned { v: 1, timestamp: i64::MAX as u64 };
+    assert_eq!(data.to_lp(), "ts v=1i 9223372036854775807");
+    let data = Unsigned { v: 1, timestamp: i64::MAX as u64 + 1 };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidTimestamp));
+    let data = Unsigned { v: 1, timestamp: u64::MAX };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidTimestamp));
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "ts")]
+    struct Wide {
+        pub v: i64,
+        #[influx3_lp(timestamp)]
+        pub timestamp: Option<i128>,
+    }
+
+    let data = Wide { v: 1, timestamp: Some(i64::MIN as i128) };
+    assert_eq!(data.to_lp(), "ts v=1i -9223372036854775808");
+    let data = Wide { v: 1, timestamp: Some(i64::MAX as i128 + 1) };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidTimestamp));
+}
+
+#[test]
+fn test_tag_guard() {
+    use influx3_lp::{LpError, TagDecision, TagGuard};
+
+    struct Guard;
+
+    impl TagGuard for Guard {
+        fn check(&self, key: &str, value: &str) -> TagDecision {
+            match key {
+                "room" if value.starts_with("tmp") => TagDecision::Drop,
+                "city" if value != "Paris" => TagDecision::Replace(String::from("other city")),
+                _ => TagDecision::Keep,
+            }
+        }
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        pub note: String,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub city: Option<String>,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        note: String::from("ok"),
+        room: String::from("Kitchen"),
+        city: Some(String::from("Paris")),
+    };
+    assert_eq!(data.to_lp_with_guard(&Guard).unwrap(),
+               "home,room=Kitchen,city=Paris temp=21,note=\"ok\"");
+
+    let data = SensorData {
+        temp: 21.0,
+        note: String::from("ok"),
+        room: String::from("tmp-1234"),
+        city: Some(String::from("Berlin")),
+    };
+    assert_eq!(data.to_lp_with_guard(&Guard).unwrap(),
+               "home,city=other\\ city temp=21,note=\"ok\"");
+
+    let data = SensorData {
+        temp: 21.0,
+        note: "A".repeat(64 * 1024 + 1),
+        room: String::from("Kitchen"),
+        city: None,
+    };
+    assert_eq!(data.to_lp_with_guard(&Guard),
+               Err(LpError::FieldTooLong { field: String::from("note") }));
+}
+
+#[test]
+fn test_batch() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let empty: Vec<SensorData> = Vec::new();
+    assert_eq!(influx3_lp::to_lp_batch(&empty), "");
+
+    let data = vec![
+        SensorData { temp: 21.0, room: String::from("Kitchen") },
+        SensorData { temp: 19.5, room: String::from("Living Room") },
+        SensorData { temp: 18.0, room: String::from("Bedroom") },
+    ];
+    assert_eq!(influx3_lp::to_lp_batch(&data),
+               "home,room=Kitchen temp=21\nhome,room=Living\\ Room temp=19.5\nhome,room=Bedroom temp=18");
+}
+
+#[test]
+fn test_lines() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let empty: Vec<SensorData> = Vec::new();
+    assert!(influx3_lp::to_lp_lines(&empty).is_empty());
+
+    let data = vec![
+        SensorData { temp: 21.0, room: String::from("Kitchen") },
+        SensorData { temp: 19.5, room: String::from("Living Room") },
+    ];
+    let lines = influx3_lp::to_lp_lines(&data);
+    assert_eq!(lines, ["home,room=Kitchen temp=21", "home,room=Living\\ Room temp=19.5"]);
+    assert_eq!(lines.join("\n"), influx3_lp::to_lp_batch(&data));
+}
+
+#[test]
+fn test_write_lp_appends() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        pub note: String,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let mut buf = String::from("existing\n");
+    let data = SensorData { temp: 21.0, note: String::from("ok"), room: String::from("Kitchen") };
+    data.write_lp(&mut buf);
+    buf.push('\n');
+    data.write_lp(&mut buf);
+    assert_eq!(buf, "existing\nhome,room=Kitchen temp=21,note=\"ok\"\nhome,room=Kitchen temp=21,note=\"ok\"");
+
+    // a failed write leaves the buffer untouched
+    let data = SensorData { temp: 21.0, note: "A".repeat(64 * 1024 + 1), room: String::from("Kitchen") };
+    let mut buf = String::from("existing");
+    struct KeepAll;
+    impl influx3_lp::TagGuard for KeepAll {
+        fn check(&self, _key: &str, _value: &str) -> influx3_lp::TagDecision {
+            influx3_lp::TagDecision::Keep
+        }
+    }
+    assert!(data.write_lp_with_guard(&mut buf, &KeepAll).is_err());
+    assert_eq!(buf, "existing");
+}
+
+#[cfg(feature = "wal")]
+#[test]
+fn test_wal_round_trip() {
+    use influx3_lp::{append_wal, WalReader};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let mut wal = Vec::new();
+    append_wal(&SensorData { temp: 21.0, room: String::from("Kitchen") }, &mut wal).unwrap();
+    append_wal(&SensorData { temp: 19.5, room: String::from("Bedroom") }, &mut wal).unwrap();
+
+    let lines: Vec<String> = WalReader::new(wal.as_slice()).map(|l| l.unwrap()).collect();
+    assert_eq!(lines, vec!["home,room=Kitchen temp=21", "home,room=Bedroom temp=19.5"]);
+
+    // a record torn by a crash is dropped, the ones before it are recovered
+    wal.truncate(wal.len() - 3);
+    let lines: Vec<String> = WalReader::new(wal.as_slice()).map(|l| l.unwrap()).collect();
+    assert_eq!(lines, vec!["home,room=Kitchen temp=21"]);
+
+    // an invalid value is an error, and leaves the log as it was
+    let len = wal.len();
+    let err = append_wal(&SensorData { temp: f32::NAN, room: String::from("Kitchen") }, &mut wal).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(wal.len(), len);
+}
+
+#[cfg(feature = "wal")]
+#[test]
+fn test_wal_corrupted_length() {
+    use influx3_lp::{append_wal, WalReader};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+    }
+
+    // a header claiming 4 GiB is read as a torn record, without allocating for it
+    let mut wal = Vec::new();
+    append_wal(&SensorData { temp: 21.0 }, &mut wal).unwrap();
+    wal.extend_from_slice(&u32::MAX.to_le_bytes());
+    wal.extend_from_slice(b"home temp=2");
+    let lines: Vec<String> = WalReader::new(wal.as_slice()).map(|l| l.unwrap()).collect();
+    assert_eq!(lines, vec!["home temp=21"]);
+}
+
+#[test]
+fn test_schema_metadata() {
+    use influx3_lp::{LpColumn, LpColumnKind};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "host")]
+    struct Memory {
+        #[influx3_lp(help = "Resident memory", unit = "bytes")]
+        pub rss: u64,
+        pub swapped: bool,
+        #[influx3_lp(tag, help = "Host name")]
+        pub host: String,
+        #[influx3_lp(timestamp)]
+        pub timestamp: i64,
+    }
+
+    assert_eq!(Memory::lp_schema(), &[
+        LpColumn { key: "rss", kind: LpColumnKind::Field, help: Some("Resident memory"), unit: Some("bytes") },
+        LpColumn { key: "swapped", kind: LpColumnKind::Field, help: None, unit: None },
+        LpColumn { key: "host", kind: LpColumnKind::Tag, help: Some("Host name"), unit: None },
+        LpColumn { key: "timestamp", kind: LpColumnKind::Timestamp, help: None, unit: None },
+    ]);
+
+    // metadata doesn't change the line
+    let data = Memory { rss: 1024, swapped: false, host: String::from("a"), timestamp: 1 };
+    assert_eq!(data.to_lp(), "host,host=a rss=1024u,swapped=false 1");
+}
+
+#[test]
+fn test_schema_with_flatten() {
+    use influx3_lp::{LpColumn, LpColumnCounts, LpColumnKind};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Location {
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(unit = "m")]
+        pub altitude: f64,
+        #[influx3_lp(timestamp)]
+        pub time: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Place {
+        #[influx3_lp(tag)]
+        pub city: String,
+        #[influx3_lp(flatten)]
+        pub location: Location,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(flatten)]
+        pub place: Place,
+        #[influx3_lp(timestamp)]
+        pub time: i64,
+    }
+
+    // flattened columns take the place of their member, their timestamp is ignored like in the line
+    let column = |key, kind, unit| LpColumn { key, kind, help: None, unit };
+    assert_eq!(SensorData::lp_schema(), &[
+        column("temp", LpColumnKind::Field, None),
+        column("city", LpColumnKind::Tag, None),
+        column("room", LpColumnKind::Tag, None),
+        column("altitude", LpColumnKind::Field, Some("m")),
+        column("time", LpColumnKind::Timestamp, None),
+    ]);
+    let tags = SensorData::lp_schema().iter().filter(|c| c.kind == LpColumnKind::Tag).count();
+    let fields = SensorData::lp_schema().iter().filter(|c| c.kind == LpColumnKind::Field).count();
+    assert_eq!((tags, fields), (SensorData::TAG_COUNT, SensorData::FIELD_COUNT));
+
+    // a static schema can't depend on a type parameter, a generic flattened member is left out
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Generic<T: Influx3Lp> {
+        pub temp: f32,
+        #[influx3_lp(flatten)]
+        pub inner: T,
+        #[influx3_lp(flatten)]
+        pub place: Place,
+    }
+
+    let keys = Generic::<Location>::lp_schema().iter().map(|c| c.key).collect::<Vec<_>>();
+    assert_eq!(keys, ["temp", "city", "room", "altitude"]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_lp_to() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "special data")]
+    struct SpecialData {
+        pub f1: String,
+        pub f2: Option<i64>,
+        pub f3: Option<u8>,
+        #[influx3_lp(tag)]
+        pub t1: String,
+        #[influx3_lp(tag)]
+        pub t2: Option<String>,
+        #[influx3_lp(timestamp)]
+        pub timestamp: Option<i64>,
+    }
+
+    let data = SpecialData {
+        f1: String::from("double quote \" and back slash\\"),
+        f2: None,
+        f3: Some(7),
+        t1: String::from("with,comma"),
+        t2: Some(String::from("with equal =")),
+        timestamp: Some(1735545600),
+    };
+
+    let mut out: Vec<u8> = Vec::new();
+    data.write_lp_to(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), data.to_lp());
+
+    let data = SpecialData { f1: "A".repeat(64 * 1024 + 1), ..data };
+    let err = data.write_lp_to(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_field_rename() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(field, name = "temp")]
+        pub temperature_celsius: f32,
+        #[influx3_lp(name = "relative humidity")]
+        pub hum: Option<f64>,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let data = SensorData {
+        temperature_celsius: 21.0,
+        hum: Some(35.9),
+        room: String::from("Kitchen"),
+    };
+    assert_eq!(data.to_lp(), "home,room=Kitchen temp=21,relative\\ humidity=35.9");
+    assert_eq!(SensorData::lp_schema()[0].key, "temp");
+}
+
+#[test]
+fn test_order_from_env() {
+    // INFLUX3_LP_TEST_ORDER is set in .cargo/config.toml
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", order_from_env = "INFLUX3_LP_TEST_ORDER")]
+    struct SensorData {
+        pub co: i32,
+        pub temp: f32,
+        pub hum: f64,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub city: String,
+    }
+
+    let data = SensorData {
+        co: 0,
+        temp: 21.0,
+        hum: 35.9,
+        room: String::from("Kitchen"),
+        city: String::from("Paris"),
+    };
+    assert_eq!(data.to_lp(), "home,city=Paris,room=Kitchen hum=35.9,temp=21,co=0i");
+}
+
+/// Split `s` at unescaped occurrences of `sep`, a backslash escapes the next character.
+/// With `quoted`, `sep` inside double quotes doesn't split, as in the field set.
+fn split_unescaped(s: &str, sep: char, quoted: bool) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+    let mut in_quotes = false;
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            parts.last_mut().unwrap().push(c);
+            if let Some(next) = chars.next() {
+                parts.last_mut().unwrap().push(next);
+            }
+        } else if c == '"' && quoted {
+            in_quotes = !in_quotes;
+            parts.last_mut().unwrap().push(c);
+        } else if c == sep && !in_quotes {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+/// Reverse tag value escaping, a backslash before `\`, `,`, `=` or space is an escape.
+fn unescape_tag(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('\\' | ',' | '=' | ' ')) => out.push(chars.next().unwrap()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Reverse string field escaping of a quoted value.
+fn unescape_field(s: &str) -> String {
+    let inner = &s[1..s.len() - 1];
+    let mut out = String::new();
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('\\' | '"')) => out.push(chars.next().unwrap()),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn test_escaping_round_trip() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub f1: String,
+        pub f2: String,
+        #[influx3_lp(tag)]
+        pub t1: String,
+        #[influx3_lp(tag)]
+        pub t2: String,
+    }
+
+    // xorshift, so failures are reproducible without extra dependencies
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let alphabet = ['a', 'Z', '0', ' ', ',', '=', '"', '\\', 'é', '水'];
+    let mut value = |min_len: u64| -> String {
+        let len = min_len + next() % 8;
+        (0..len).map(|_| alphabet[(next() % alphabet.len() as u64) as usize]).collect()
+    };
+
+    for _ in 0..1000 {
+        let data = SensorData { f1: value(0), f2: value(0), t1: value(1), t2: value(1) };
+        let line = data.to_lp();
+
+        // measurement+tags, fields
+        let (head, field_set) = line.split_at(split_unescaped(&line, ' ', false)[0].len());
+        let tags = split_unescaped(head, ',', false);
+        assert_eq!(tags[0], "home", "{}", line);
+        assert_eq!(tags[1..].iter().map(|t| unescape_tag(&t[3..])).collect::<Vec<_>>(),
+                   vec![data.t1.clone(), data.t2.clone()],
+                   "{}", line);
+
+        let fields = split_unescaped(&field_set[1..], ',', true);
+        assert_eq!(fields.iter().map(|f| unescape_field(&f[3..])).collect::<Vec<_>>(),
+                   vec![data.f1.clone(), data.f2.clone()],
+                   "{}", line);
+    }
+}
+
+#[test]
+fn test_tag_rename() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "cpu")]
+    struct CpuData {
+        pub usage: f64,
+        #[influx3_lp(tag, name = "host")]
+        pub hostname: String,
+        #[influx3_lp(tag, name = "data center")]
+        pub dc: Option<String>,
+    }
+
+    let data = CpuData {
+        usage: 0.5,
+        hostname: String::from("server01"),
+        dc: Some(String::from("eu-1")),
+    };
+    assert_eq!(data.to_lp(), "cpu,host=server01,data\\ center=eu-1 usage=0.5");
+
+    let data = CpuData { dc: None, ..data };
+    assert_eq!(data.to_lp(), "cpu,host=server01 usage=0.5");
+}
+
+#[test]
+fn test_skip() {
+    #[allow(dead_code)]
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(skip)]
+        pub id: u64,
+        pub temp: f32,
+        #[influx3_lp(skip)]
+        pub cached: Vec<f32>,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let data = SensorData {
+        id: 42,
+        temp: 21.0,
+        cached: vec![1.0, 2.0],
+        room: String::from("Kitchen"),
+    };
+    assert_eq!(data.to_lp(), "home,room=Kitchen temp=21");
+    assert_eq!(SensorData::lp_schema().len(), 2);
+}
+
+#[test]
+fn test_coalesce() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    #[influx3_lp(coalesce(key = "temp", primary, secondary, fallback))]
+    struct SensorData {
+        pub hum: f64,
+        pub primary: Option<f32>,
+        pub secondary: Option<f32>,
+        pub fallback: Option<f32>,
+        pub co: i32,
+    }
+
+    let data = SensorData { hum: 35.9, primary: Some(21.0), secondary: Some(22.0), fallback: Some(23.0), co: 0 };
+    assert_eq!(data.to_lp(), "home hum=35.9,temp=21,co=0i");
+
+    let data = SensorData { primary: None, ..data };
+    assert_eq!(data.to_lp(), "home hum=35.9,temp=22,co=0i");
+
+    let data = SensorData { secondary: None, ..data };
+    assert_eq!(data.to_lp(), "home hum=35.9,temp=23,co=0i");
+
+    let data = SensorData { fallback: None, ..data };
+    assert_eq!(data.to_lp(), "home hum=35.9,co=0i");
+
+    // the schema has the metadata of the first field
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", coalesce(primary, fallback))]
+    struct Described {
+        #[influx3_lp(name = "temp", help = "room temperature", unit = "celsius")]
+        pub primary: Option<f32>,
+        pub fallback: Option<f32>,
+    }
+
+    let schema = Described::lp_schema();
+    assert_eq!(schema.len(), 1);
+    assert_eq!((schema[0].key, schema[0].help, schema[0].unit), ("temp", Some("room temperature"), Some("celsius")));
+}
+
+#[test]
+fn test_extra_tags() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub city: Option<String>,
+    }
+
+    let data = SensorData { temp: 21.0, room: String::from("Kitchen"), city: None };
+    assert_eq!(data.to_lp_with_tags(&[]), data.to_lp());
+    assert_eq!(data.to_lp_with_tags(&[("trace id", "a,b"), ("deployment", "blue")]),
+               "home,room=Kitchen,trace\\ id=a\\,b,deployment=blue temp=21");
+}
+
+#[test]
+#[should_panic(expected = "Tag key `room` is used more than once")]
+fn test_extra_tags_collision() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let data = SensorData { temp: 21.0, room: String::from("Kitchen") };
+    data.to_lp_with_tags(&[("room", "Bedroom")]);
+}
+
+#[test]
+fn test_extra_tags_collision_with_nested_tags() {
+    use std::collections::BTreeMap;
+    use std::panic::catch_unwind;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Location {
+        #[influx3_lp(tag)]
+        pub room: String,
+        pub floor: u8,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(flatten)]
+        pub location: Location,
+        #[influx3_lp(tags)]
+        pub meta: BTreeMap<String, String>,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        location: Location { room: String::from("k"), floor: 1 },
+        meta: BTreeMap::from([(String::from("m"), String::from("1"))]),
+    };
+    assert_eq!(data.to_lp_with_tags(&[("trace", "a")]), "home,room=k,m=1,trace=a temp=21,floor=1u");
+    assert!(catch_unwind(|| data.to_lp_with_tags(&[("room", "dup")])).is_err());
+    assert!(catch_unwind(|| data.to_lp_with_tags(&[("m", "dup")])).is_err());
+    assert!(catch_unwind(|| data.to_lp_with_tags(&[("trace", "a"), ("trace", "b")])).is_err());
+}
+
+#[test]
+fn test_extra_tags_sorted() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", sort_tags)]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub zz: String,
+        #[influx3_lp(tag)]
+        pub mm: String,
+    }
+
+    let data = SensorData { temp: 21.0, zz: String::from("z"), mm: String::from("m") };
+    assert_eq!(data.to_lp(), "home,mm=m,zz=z temp=21");
+    assert_eq!(data.to_lp_with_tags(&[("aa", "1"), ("n\\ o", "2")]), "home,aa=1,mm=m,n\\\\\\ o=2,zz=z temp=21");
+}
+
+#[test]
+fn test_timestamp_override() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(timestamp)]
+        pub time: Option<i64>,
+    }
+
+    let data = SensorData { temp: 21.0, time: Some(1735545600) };
+    assert_eq!(data.to_lp_with_timestamp(1735549200), "home temp=21 1735549200");
+    let data = SensorData { temp: 21.0, time: None };
+    assert_eq!(data.to_lp_with_timestamp(1735549200), "home temp=21 1735549200");
+    assert_eq!(data.to_lp(), "home temp=21");
+}
+
+#[test]
+fn test_rename_all() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", rename_all = "camelCase")]
+    struct Camel {
+        pub air_temp: f32,
+        #[influx3_lp(name = "hum")]
+        pub relative_humidity: f64,
+        #[influx3_lp(tag)]
+        pub room_name: String,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", rename_all = "PascalCase")]
+    struct Pascal {
+        pub air_temp: f32,
+        #[influx3_lp(tag)]
+        pub room_name: String,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", rename_all = "kebab-case")]
+    struct Kebab {
+        pub air_temp: f32,
+        #[influx3_lp(tag)]
+        pub room_name: String,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", rename_all = "snake_case")]
+    struct Snake {
+        pub air_temp: f32,
+        #[influx3_lp(tag)]
+        pub room_name: String,
+    }
+
+    let data = Camel { air_temp: 21.0, relative_humidity: 35.9, room_name: String::from("Kitchen") };
+    assert_eq!(data.to_lp(), "home,roomName=Kitchen airTemp=21,hum=35.9");
+    let data = Pascal { air_temp: 21.0, room_name: String::from("Kitchen") };
+    assert_eq!(data.to_lp(), "home,RoomName=Kitchen AirTemp=21");
+    let data = Kebab { air_temp: 21.0, room_name: String::from("Kitchen") };
+    assert_eq!(data.to_lp(), "home,room-name=Kitchen air-temp=21");
+    let data = Snake { air_temp: 21.0, room_name: String::from("Kitchen") };
+    assert_eq!(data.to_lp(), "home,room_name=Kitchen air_temp=21");
+}
+
+#[test]
+fn test_numeric_and_bool_tags() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub sensor_id: u32,
+        #[influx3_lp(tag)]
+        pub floor: i8,
+        #[influx3_lp(tag)]
+        pub active: bool,
+        #[influx3_lp(tag)]
+        pub zone: Option<u64>,
+    }
+
+    let data = SensorData { temp: 21.0, sensor_id: 42, floor: -1, active: true, zone: Some(7) };
+    assert_eq!(data.to_lp(), "home,sensor_id=42,floor=-1,active=true,zone=7 temp=21");
+
+    let data = SensorData { active: false, zone: None, ..data };
+    assert_eq!(data.to_lp(), "home,sensor_id=42,floor=-1,active=false temp=21");
+}
+
+#[test]
+fn test_parse_line() {
+    use influx3_lp::{parse_line, FieldValue, LineParts};
+
+    let parts = parse_line("special\\ data,t1=with\\,comma,t2=with\\ equal\\ \\=,t3=back\\\\ f1=\"with,comma\",f2=\"quote \\\" and slash\\\\\",f3=-1.5,f4=3i,f5=4u,f6=t 1735545600").unwrap();
+    assert_eq!(parts, LineParts {
+        measurement: String::from("special data"),
+        tags: vec![
+            (String::from("t1"), String::from("with,comma")),
+            (String::from("t2"), String::from("with equal =")),
+            (String::from("t3"), String::from("back\\")),
+        ],
+        fields: vec![
+            (String::from("f1"), FieldValue::String(String::from("with,comma"))),
+            (String::from("f2"), FieldValue::String(String::from("quote \" and slash\\"))),
+            (String::from("f3"), FieldValue::Float(-1.5)),
+            (String::from("f4"), FieldValue::Integer(3)),
+            (String::from("f5"), FieldValue::UInteger(4)),
+            (String::from("f6"), FieldValue::Boolean(true)),
+        ],
+        timestamp: Some(1735545600),
+    });
+
+    assert!(parse_line("home").is_err());
+    assert!(parse_line("home temp").is_err());
+    assert!(parse_line("home temp=abc").is_err());
+    assert!(parse_line("home temp=\"open").is_err());
+    assert!(parse_line("home temp=1 now").is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_lp_reader() {
+    use influx3_lp::lp_reader;
+
+    let body = "# exported from home\nhome,room=Kitchen temp=21 1\n\n   \nhome,room=Kitchen temp= 2\nhome,room=Bedroom temp=19.5 3\r\n";
+    let results: Vec<_> = lp_reader(body.as_bytes()).collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().timestamp, Some(1));
+    assert_eq!(results[1].as_ref().unwrap_err().line, 5);
+    assert_eq!(results[2].as_ref().unwrap().tags[0].1, "Bedroom");
+}
+
+#[test]
+fn test_borrowed_string_fields() {
+    use std::borrow::Cow;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log")]
+    struct LogLine<'a> {
+        pub message: &'a str,
+        pub source: Cow<'a, str>,
+        pub host: Box<str>,
+        pub level: Option<&'a str>,
+    }
+
+    let line = String::from("disk \"sda\" at 90%\\");
+    let data = LogLine {
+        message: &line,
+        source: Cow::Borrowed("kernel"),
+        host: Box::from("server 01"),
+        level: Some("warn"),
+    };
+    assert_eq!(data.to_lp(),
+               "log message=\"disk \\\"sda\\\" at 90%\\\\\",source=\"kernel\",host=\"server 01\",level=\"warn\"");
+
+    let long = "A".repeat(64 * 1024 + 1);
+    let data = LogLine { message: &long, source: Cow::Owned(String::new()), host: Box::from(""), level: None };
+    assert!(data.to_lp_with_guard(&influx3_lp::KeepAll).is_err());
+}
+
+#[test]
+fn test_invalid_float() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+        pub hum: Option<f32>,
+    }
+
+    let data = SensorData { temp: f64::NAN, hum: None };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFloat { field: String::from("temp") }));
+    let data = SensorData { temp: f64::INFINITY, hum: None };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFloat { field: String::from("temp") }));
+    let data = SensorData { temp: 21.0, hum: Some(f32::NEG_INFINITY) };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFloat { field: String::from("hum") }));
+
+    let data = SensorData { temp: -0.0, hum: Some(0.0) };
+    assert_eq!(data.try_to_lp().unwrap(), "home temp=-0,hum=0");
+}
+
+#[test]
+#[should_panic(expected = "Float field value must be finite")]
+fn test_invalid_float_panics() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+    }
+
+    SensorData { temp: f64::NAN }.to_lp();
+}
+
+#[test]
+fn test_newlines_rejected() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log")]
+    struct LogLine {
+        pub message: String,
+        #[influx3_lp(tag)]
+        pub host: String,
+    }
+
+    let data = LogLine { message: String::from("first\nsecond"), host: String::from("a") };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFieldValue { field: String::from("message") }));
+
+    let data = LogLine { message: String::from("first\r"), host: String::from("a") };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFieldValue { field: String::from("message") }));
+
+    let data = LogLine { message: String::from("ok"), host: String::from("a\nb") };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidTagValue { tag: String::from("host") }));
+
+    let data = LogLine { message: String::from("ok"), host: String::from("a") };
+    assert_eq!(data.try_to_lp().unwrap(), "log,host=a message=\"ok\"");
+}
+
+#[test]
+fn test_empty_tag_value() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub floor: Option<String>,
+        #[influx3_lp(tag)]
+        pub city: String,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        room: String::new(),
+        floor: Some(String::new()),
+        city: String::from("Paris"),
+    };
+    assert_eq!(data.to_lp(), "home,city=Paris temp=21");
+    assert_eq!(data.to_lp_with_tags(&[("trace", "")]), "home,city=Paris temp=21");
+}
+
+#[test]
+fn test_all_fields_none() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: Option<f32>,
+        pub hum: Option<f64>,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let data = SensorData { temp: None, hum: None, room: String::from("Kitchen") };
+    assert_eq!(data.try_to_lp(), Err(LpError::EmptyFieldSet));
+
+    let mut buf = String::from("existing");
+    assert!(data.write_lp_with_guard(&mut buf, &influx3_lp::KeepAll).is_err());
+    assert_eq!(buf, "existing");
+}
+
+#[test]
+fn test_sort_tags() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", sort_tags)]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub floor: u8,
+        #[influx3_lp(tag, name = "building")]
+        pub house: String,
+    }
+
+    let data = SensorData { temp: 21.0, room: String::from("Kitchen"), floor: 2, house: String::from("A") };
+    assert_eq!(data.to_lp(), "home,building=A,floor=2,room=Kitchen temp=21");
+}
+
+#[test]
+fn test_sort_tags_with_nested_tags() {
+    use std::collections::BTreeMap;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Location {
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub building: String,
+        pub floor: u8,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", sort_tags)]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub city: String,
+        #[influx3_lp(flatten)]
+        pub location: Location,
+        #[influx3_lp(tags)]
+        pub meta: BTreeMap<String, String>,
+    }
+
+    // every key takes its place, not the member holding it
+    let data = SensorData {
+        temp: 21.0,
+        city: String::from("Paris"),
+        location: Location { room: String::from("Kitchen"), building: String::from("A"), floor: 2 },
+        meta: BTreeMap::from([(String::from("zone"), String::from("north")), (String::from("area"), String::from("x"))]),
+    };
+    assert_eq!(data.to_lp(), "home,area=x,building=A,city=Paris,room=Kitchen,zone=north temp=21,floor=2u");
+}
+
+#[test]
+fn test_field_prefix() {
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "host", field_prefix = "cpu_", coalesce(primary, fallback))]
+    struct Cpu {
+        pub usage: f64,
+        #[influx3_lp(field, name = "load")]
+        pub load_average: f64,
+        pub primary: Option<u8>,
+        pub fallback: Option<u8>,
+        #[influx3_lp(tag)]
+        pub host: String,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "host", field_prefix = "mem_", field_suffix = "_bytes", rename_all = "camelCase")]
+    struct Memory {
+        pub resident_set: u64,
+        #[influx3_lp(tag)]
+        pub host_name: String,
+    }
+
+    let data = Cpu { usage: 0.5, load_average: 1.5, primary: None, fallback: Some(3), host: String::from("a") };
+    assert_eq!(data.to_lp(), "host,host=a cpu_usage=0.5,load=1.5,cpu_primary=3u");
+    assert_eq!(Cpu::from_lp("host,host=a cpu_usage=0.5,load=1.5,cpu_primary=3u").unwrap().usage, 0.5);
+
+    let data = Memory { resident_set: 1024, host_name: String::from("a") };
+    assert_eq!(data.to_lp(), "host,hostName=a mem_residentSet_bytes=1024u");
+}
+
+#[test]
+fn test_sort_fields() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", sort_fields)]
+    struct SensorData {
+        pub temp: f32,
+        pub co: Option<i32>,
+        #[influx3_lp(field, name = "humidity")]
+        pub hum: f64,
+        #[influx3_lp(tag)]
+        pub room: String,
+        pub alarm: bool,
+    }
+
+    let data = SensorData { temp: 21.0, co: Some(0), hum: 35.9, room: String::from("Kitchen"), alarm: false };
+    assert_eq!(data.to_lp(), "home,room=Kitchen alarm=false,co=0i,humidity=35.9,temp=21");
+    let data = SensorData { co: None, ..data };
+    assert_eq!(data.to_lp(), "home,room=Kitchen alarm=false,humidity=35.9,temp=21");
+}
+
+#[test]
+fn test_column_counts() {
+    use std::collections::HashMap;
+    use influx3_lp::LpColumnCounts;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Location {
+        #[influx3_lp(tag)]
+        pub city: String,
+        pub altitude: f64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home", coalesce(key = "temp", primary, fallback))]
+    struct SensorData {
+        pub primary: Option<f32>,
+        pub fallback: Option<f32>,
+        pub co: i32,
+        #[influx3_lp(flatten)]
+        pub location: Location,
+        #[influx3_lp(fields)]
+        pub extra: HashMap<String, f64>,
+        #[influx3_lp(tag)]
+        pub room: Option<String>,
+        #[influx3_lp(skip)]
+        #[allow(dead_code)]
+        pub cache: Vec<u8>,
+        #[influx3_lp(timestamp)]
+        pub time: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "event")]
+    #[allow(dead_code)]
+    enum Event {
+        Login { user: String },
+        Reading {
+            #[influx3_lp(tag)]
+            host: String,
+            #[influx3_lp(tag)]
+            room: String,
+            value: f64,
+        },
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "reading")]
+    struct Generic<L: Influx3Lp> {
+        pub value: f64,
+        #[influx3_lp(flatten)]
+        pub location: L,
+    }
+
+    assert_eq!(Location::TAG_COUNT, 1);
+    assert_eq!(Location::FIELD_COUNT, 1);
+    assert_eq!(SensorData::TAG_COUNT, 2);
+    assert_eq!(SensorData::FIELD_COUNT, 3);
+    assert_eq!(Event::TAG_COUNT, 2);
+    assert_eq!(Event::FIELD_COUNT, 1);
+    assert_eq!(Generic::<Location>::FIELD_COUNT, 2);
+}
+
+#[test]
+fn test_float_precision() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(precision = 3)]
+        pub temp: f64,
+        #[influx3_lp(field, precision = 2)]
+        pub hum: Option<f32>,
+        pub co: f64,
+    }
+
+    let data = SensorData { temp: 0.1 + 0.2, hum: Some(21.0), co: 0.1 + 0.2 };
+    assert_eq!(data.to_lp(), "home temp=0.300,hum=21.00,co=0.30000000000000004");
+
+    let data = SensorData { temp: f64::NAN, hum: None, co: 1.0 };
+    assert_eq!(data.try_to_lp(), Err(influx3_lp::LpError::InvalidFloat { field: String::from("temp") }));
+}
+
+#[test]
+fn test_float_style() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+        #[influx3_lp(field, float_style = "explicit")]
+        pub hum: f64,
+        #[influx3_lp(field, float_style = "explicit")]
+        pub co: Option<f32>,
+    }
+
+    let data = SensorData { temp: 21.0, hum: 21.0, co: Some(-3.0) };
+    assert_eq!(data.to_lp(), "home temp=21,hum=21.0,co=-3.0");
+    let data = SensorData { temp: 21.5, hum: 21.5, co: Some(0.25) };
+    assert_eq!(data.to_lp(), "home temp=21.5,hum=21.5,co=0.25");
+    let data = SensorData { temp: 1e20, hum: 1e20, co: None };
+    assert_eq!(data.to_lp(), "home temp=100000000000000000000,hum=100000000000000000000.0");
+
+    let data = SensorData { temp: 1.0, hum: f64::INFINITY, co: None };
+    assert_eq!(data.try_to_lp(), Err(influx3_lp::LpError::InvalidFloat { field: String::from("hum") }));
+}
+
+#[test]
+fn test_duration_unit() {
+    use std::time::Duration;
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "op")]
+    struct Operation {
+        #[influx3_lp(field, duration_unit = "ns")]
+        pub ns: Duration,
+        #[influx3_lp(field, duration_unit = "us")]
+        pub us: Duration,
+        #[influx3_lp(field, duration_unit = "ms")]
+        pub ms: Option<Duration>,
+        #[influx3_lp(field, duration_unit = "s")]
+        pub s: Option<Duration>,
+    }
+
+    let d = Duration::new(3, 456_789_012);
+    let data = Operation { ns: d, us: d, ms: Some(d), s: Some(d) };
+    assert_eq!(data.to_lp(), "op ns=3456789012i,us=3456789i,ms=3456i,s=3i");
+    let data = Operation { ns: Duration::ZERO, us: Duration::from_nanos(999), ms: None, s: None };
+    assert_eq!(data.to_lp(), "op ns=0i,us=0i");
+
+    // u64::MAX seconds is too many nanoseconds for an i64
+    let data = Operation { ns: Duration::MAX, us: d, ms: None, s: Some(Duration::MAX) };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("ns") }));
+}
+
+#[test]
+fn test_float_without_exponent() {
+    use influx3_lp::{parse_line, FieldValue};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+    }
+
+    assert_eq!(SensorData { temp: 1e20 }.to_lp(), "home temp=100000000000000000000");
+    assert_eq!(SensorData { temp: -1e-10 }.to_lp(), "home temp=-0.0000000001");
+
+    let subnormal = SensorData { temp: f64::from_bits(1) }.to_lp();
+    let value = subnormal.strip_prefix("home temp=").unwrap();
+    assert!(!value.contains(['e', 'E']));
+    assert!(value.starts_with("0.0000"));
+    assert!(subnormal.ends_with("0005"));
+    assert_eq!(parse_line(&subnormal).unwrap().fields[0].1, FieldValue::Float(f64::from_bits(1)));
+}
+
+#[test]
+fn test_line_builder() {
+    use influx3_lp::{LineBuilder, LpError};
+
+    let line = LineBuilder::new("home sensors")
+        .tag("room", "Kitchen")
+        .tag("floor", "")
+        .field_float("temp", 21.5)
+        .field_uint("co", 3)
+        .field_bool("on", true)
+        .field_str("note", "say \"hi\"")
+        .build()
+        .unwrap();
+    assert_eq!(line, "home\\ sensors,room=Kitchen temp=21.5,co=3u,on=true,note=\"say \\\"hi\\\"\"");
+
+    let line = LineBuilder::new("home").tag("room", "Kitchen").build();
+    assert_eq!(line, Err(LpError::EmptyFieldSet));
+
+    let line = LineBuilder::new("home").field_float("temp", f64::NAN).field_int("co", 1).build();
+    assert_eq!(line, Err(LpError::InvalidFloat { field: String::from("temp") }));
+
+    let line = LineBuilder::new("home").tag("room", "a\nb").field_int("co", 1).build();
+    assert_eq!(line, Err(LpError::InvalidTagValue { tag: String::from("room") }));
+}
+
+#[test]
+fn test_line_builder_invalid_names() {
+    use influx3_lp::{LineBuilder, LpError};
+
+    let invalid_measurement = |name: &str| Err(LpError::InvalidMeasurement { name: String::from(name) });
+    for name in ["", "_internal", "home\nroom"] {
+        assert_eq!(LineBuilder::new(name).field_int("co", 1).build(), invalid_measurement(name));
+    }
+
+    let invalid_key = |key: &str| Err(LpError::InvalidKey { key: String::from(key) });
+    assert_eq!(LineBuilder::new("home").field_int("", 1).build(), invalid_key(""));
+    assert_eq!(LineBuilder::new("home").field_int("time", 1).build(), invalid_key("time"));
+    assert_eq!(LineBuilder::new("home").tag("_time", "a").field_int("co", 1).build(), invalid_key("_time"));
+    // the key is checked even when the empty value leaves the tag out
+    assert_eq!(LineBuilder::new("home").tag("", "").field_int("co", 1).build(), invalid_key(""));
+    assert_eq!(LineBuilder::new("home").tag("a\nb", "x").field_int("co", 1).build(), invalid_key("a\nb"));
+}
+
+#[test]
+fn test_from_lp() {
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "home", rename_all = "camelCase")]
+    struct SensorData {
+        pub room_temp: f64,
+        pub hum: Option<f32>,
+        pub co: i64,
+        pub level: u8,
+        pub on: bool,
+        pub note: String,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag, name = "floor")]
+        pub floor_number: u8,
+        #[influx3_lp(skip)]
+        pub cache: Vec<u8>,
+        #[influx3_lp(timestamp)]
+        pub timestamp: Option<i64>,
+    }
+
+    let data = SensorData {
+        room_temp: 21.5,
+        hum: None,
+        co: -3,
+        level: 7,
+        on: true,
+        note: String::from("say \"hi\", \\ bye"),
+        room: String::from("Living Room"),
+        floor_number: 2,
+        cache: Vec::new(),
+        timestamp: Some(1735545600),
+    };
+    assert_eq!(SensorData::from_lp(&data.to_lp()), Ok(data));
+
+    let error = |line: &str| match SensorData::from_lp(line) {
+        Err(LpError::Parse(e)) => e.message,
+        other => panic!("expected a parse error, got {:?}", other),
+    };
+    assert_eq!(error("office,room=A,floor=1 roomTemp=1,co=1i,level=1u,on=t,note=\"\""),
+               "measurement `office` doesn't match `home`");
+    assert_eq!(error("home,room=A,floor=1,wing=B roomTemp=1,co=1i,level=1u,on=t,note=\"\""),
+               "unknown tag `wing`");
+    assert_eq!(error("home,room=A,floor=1 roomTemp=1,co=1i,level=1u,on=t,note=\"\",x=1"),
+               "unknown field `x`");
+    assert_eq!(error("home,room=A,floor=1 roomTemp=1,co=1u,level=1u,on=t,note=\"\""),
+               "field `co` has the wrong type");
+    assert_eq!(error("home,room=A,floor=1 roomTemp=1,co=1i,level=300u,on=t,note=\"\""),
+               "field `level` has the wrong type");
+    assert_eq!(error("home,room=A,floor=first roomTemp=1,co=1i,level=1u,on=t,note=\"\""),
+               "invalid value `first` of tag `floor`");
+    assert_eq!(error("home,room=A roomTemp=1,co=1i,level=1u,on=t,note=\"\""),
+               "missing tag `floor`");
+    assert_eq!(error("home,room=A,floor=1 co=1i,level=1u,on=t,note=\"\""),
+               "missing field `roomTemp`");
+    assert_eq!(error("home,room=A,floor=1"), "missing field set");
+}
+
+#[test]
+fn test_field_map() {
+    use std::collections::{BTreeMap, HashMap};
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+        #[influx3_lp(fields)]
+        pub metrics: HashMap<String, f64>,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let metrics = HashMap::from([
+        (String::from("voc"), 0.5),
+        (String::from("co 2"), 412.0),
+        (String::from("pm25"), 8.25),
+    ]);
+    let data = SensorData { temp: 21.0, metrics, room: String::from("Kitchen") };
+    let line = data.to_lp();
+    assert_eq!(line, "home,room=Kitchen temp=21,co\\ 2=412,pm25=8.25,voc=0.5");
+    assert_eq!(SensorData::from_lp(&line), Ok(data));
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Dynamic {
+        #[influx3_lp(fields)]
+        pub metrics: BTreeMap<&'static str, i64>,
+    }
+
+    let data = Dynamic { metrics: BTreeMap::from([("b", 2), ("a", 1)]) };
+    assert_eq!(data.to_lp(), "home a=1i,b=2i");
+    let data = Dynamic { metrics: BTreeMap::new() };
+    assert_eq!(data.try_to_lp(), Err(LpError::EmptyFieldSet));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_flatten() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "location")]
+    struct Location {
+        #[influx3_lp(tag)]
+        pub city: String,
+        #[influx3_lp(tag)]
+        pub room: Option<String>,
+        pub altitude: f64,
+        #[influx3_lp(timestamp)]
+        pub updated_at: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(flatten)]
+        pub location: Location,
+        #[influx3_lp(tag)]
+        pub sensor: String,
+        #[influx3_lp(timestamp)]
+        pub timestamp: i64,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        location: Location { city: String::from("Paris"), room: None, altitude: 35.0, updated_at: 1 },
+        sensor: String::from("s1"),
+        timestamp: 1735545600,
+    };
+    assert_eq!(data.to_lp(), "home,city=Paris,sensor=s1 temp=21,altitude=35 1735545600");
+
+    let mut out = Vec::new();
+    data.write_lp_to(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), data.to_lp());
+}
+
+#[test]
+fn test_flatten_duplicate_tag_key() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "inner")]
+    struct Inner {
+        #[influx3_lp(tag)]
+        pub host: String,
+        pub w: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "outer")]
+    struct Outer {
+        #[influx3_lp(tag)]
+        pub host: String,
+        pub v: i64,
+        #[influx3_lp(flatten)]
+        pub inner: Inner,
+    }
+
+    let data = Outer { host: String::from("a"), v: 1, inner: Inner { host: String::from("b"), w: 2 } };
+    assert_eq!(data.try_to_lp(), Err(LpError::DuplicateTagKey { tag: String::from("host") }));
+
+    // a tag left out isn't a duplicate
+    let data = Outer { inner: Inner { host: String::new(), w: 2 }, ..data };
+    assert_eq!(data.to_lp(), "outer,host=a v=1i,w=2i");
+}
+
+#[test]
+fn test_flatten_duplicate_field_key() {
+    use influx3_lp::LpError;
+    use std::collections::BTreeMap;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "inner")]
+    struct Inner {
+        pub note: String,
+        pub v: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "outer")]
+    struct Outer {
+        pub v: i64,
+        #[influx3_lp(flatten)]
+        pub inner: Inner,
+    }
+
+    let data = Outer { v: 1, inner: Inner { note: String::from("x"), v: 2 } };
+    assert_eq!(data.try_to_lp(), Err(LpError::DuplicateFieldKey { field: String::from("v") }));
+
+    // a comma or `=` inside a string value doesn't start a key
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "outer")]
+    struct Other {
+        pub n: i64,
+        #[influx3_lp(flatten)]
+        pub inner: Inner,
+    }
+
+    let data = Other { n: 1, inner: Inner { note: String::from("a,v=\"b"), v: 2 } };
+    assert_eq!(data.to_lp(), "outer n=1i,note=\"a,v=\\\"b\",v=2i");
+
+    // a fields map is checked the same way
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "outer")]
+    struct Mapped {
+        pub v: i64,
+        #[influx3_lp(fields)]
+        pub extra: BTreeMap<String, i64>,
+    }
+
+    let data = Mapped { v: 1, extra: BTreeMap::from([(String::from("v"), 2)]) };
+    assert_eq!(data.try_to_lp(), Err(LpError::DuplicateFieldKey { field: String::from("v") }));
+}
+
+#[test]
+fn test_nested_option() {
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        pub co: Option<Option<i64>>,
+        #[influx3_lp(tag)]
+        pub room: Option<Option<String>>,
+    }
+
+    let data = SensorData { temp: 21.0, co: None, room: None };
+    assert_eq!(data.to_lp(), "home temp=21");
+    assert_eq!(SensorData::from_lp("home temp=21"), Ok(data));
+
+    let data = SensorData { temp: 21.0, co: Some(None), room: Some(None) };
+    assert_eq!(data.to_lp(), "home temp=21");
+
+    let data = SensorData { temp: 21.0, co: Some(Some(5)), room: Some(Some(String::from("Kitchen"))) };
+    assert_eq!(data.to_lp(), "home,room=Kitchen temp=21,co=5i");
+    assert_eq!(SensorData::from_lp("home,room=Kitchen temp=21,co=5i"), Ok(data));
+}
+
+#[test]
+fn test_generic_struct() {
+    use std::str::FromStr;
+    use influx3_lp::{FromFieldValue, FromLp, LpFieldValue, LpTagValue};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "reading")]
+    struct Reading<W, B: LpTagValue + FromStr>
+    where
+        W: LpFieldValue + FromFieldValue,
+    {
+        pub value: W,
+        pub previous: Option<W>,
+        #[influx3_lp(tag)]
+        pub sensor: B,
+    }
+
+    let data = Reading { value: 21.5f64, previous: None, sensor: 7u8 };
+    assert_eq!(data.to_lp(), "reading,sensor=7 value=21.5");
+    assert_eq!(Reading::from_lp("reading,sensor=7 value=21.5"), Ok(data));
+
+    let data = Reading { value: 3i64, previous: Some(2), sensor: String::from("s1") };
+    assert_eq!(data.to_lp(), "reading,sensor=s1 value=3i,previous=2i");
+}
+
+#[test]
+fn test_enum() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "event", rename_all = "camelCase")]
+    enum Event {
+        #[influx3_lp(table_name = "door")]
+        Door {
+            #[influx3_lp(tag)]
+            door_id: String,
+            is_open: bool,
+            #[influx3_lp(timestamp)]
+            at: i64,
+        },
+        Alarm {
+            #[influx3_lp(tag)]
+            room: Option<String>,
+            level: u8,
+            message: String,
+        },
+    }
+
+    let door = Event::Door { door_id: String::from("front"), is_open: true, at: 1735545600 };
+    assert_eq!(door.to_lp(), "door,doorId=front isOpen=true 1735545600");
+
+    let alarm = Event::Alarm { room: None, level: 3, message: String::from("smoke") };
+    assert_eq!(alarm.to_lp(), "event level=3u,message=\"smoke\"");
+    assert_eq!(influx3_lp::to_lp_batch(&[door, alarm]).lines().count(), 2);
+    assert_eq!(Event::lp_schema().len(), 6);
+}
+
+#[test]
+fn test_table_name_member() {
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    struct Metric {
+        #[influx3_lp(table_name)]
+        pub table: String,
+        pub value: f64,
+        #[influx3_lp(tag)]
+        pub host: String,
+    }
+
+    let cpu = Metric { table: String::from("cpu"), value: 0.5, host: String::from("a") };
+    let mem = Metric { table: String::from("mem usage"), value: 0.25, host: String::from("a") };
+    assert_eq!(cpu.to_lp(), "cpu,host=a value=0.5");
+    assert_eq!(mem.to_lp(), "mem\\ usage,host=a value=0.25");
+    assert_eq!(Metric::from_lp(&mem.to_lp()), Ok(mem));
+
+    let internal = Metric { table: String::from("_internal"), value: 1.0, host: String::from("a") };
+    assert_eq!(internal.try_to_lp(), Err(LpError::InvalidMeasurement { name: String::from("_internal") }));
+
+    #[derive(Influx3Lp)]
+    enum Event<'a> {
+        Custom {
+            #[influx3_lp(table_name)]
+            kind: &'a str,
+            count: u32,
+        },
+    }
+
+    assert_eq!(Event::Custom { kind: "door", count: 2 }.to_lp(), "door count=2u");
+}
+
+#[test]
+fn test_size_hint() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        pub hum: Option<f64>,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(timestamp)]
+        pub timestamp: i64,
+    }
+
+    let data = SensorData { temp: 21.0, hum: Some(35.9), room: String::from("Kitchen"), timestamp: 1735545600 };
+    let line = data.to_lp();
+    // a typical line fits, so to_lp allocates once
+    assert!(data.__size_hint() >= line.len());
+    assert_eq!(line.capacity(), data.__size_hint());
+}
+
+#[test]
+fn test_single_pass_escaping() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub note: String,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let values = ["", "plain", "a,b c=d", "\\,\\", "\"é\" \\\" ü=,", ",,  ==", "日本 語"];
+    for value in values {
+        let data = SensorData { note: value.to_string(), room: format!("x{}", value) };
+        let tag = data.room.replace('\\', "\\\\").replace(',', "\\,").replace(' ', "\\ ").replace('=', "\\=");
+        let note = value.replace('\\', "\\\\").replace('"', "\\\"");
+        assert_eq!(data.to_lp(), format!("home,room={} note=\"{}\"", tag, note));
+    }
+}
+
+#[test]
+fn test_bool_format() {
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "door", bool_format = "short")]
+    struct Short {
+        pub open: bool,
+        pub locked: Option<bool>,
+        pub count: u8,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "door", bool_format = "long")]
+    struct Long {
+        pub open: bool,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "door")]
+    struct Unset {
+        pub open: bool,
+    }
+
+    let data = Short { open: true, locked: Some(false), count: 2 };
+    assert_eq!(data.to_lp(), "door open=t,locked=f,count=2u");
+    assert_eq!(Short::from_lp("door open=t,locked=f,count=2u"), Ok(data));
+    assert_eq!(Long { open: false }.to_lp(), "door open=false");
+    assert_eq!(Unset { open: true }.to_lp(), "door open=true");
+
+    // the values of a fields map are written like the named fields
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "door", bool_format = "short")]
+    struct Mapped {
+        pub open: bool,
+        #[influx3_lp(fields)]
+        pub sensors: std::collections::BTreeMap<String, bool>,
+    }
+
+    let data = Mapped { open: true, sensors: [(String::from("a"), false), (String::from("b"), true)].into() };
+    assert_eq!(data.to_lp(), "door open=t,a=f,b=t");
+}
+
+#[test]
+fn test_unsigned_forbid() {
+    use std::num::NonZeroU32;
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "disk", unsigned = "forbid", bool_format = "short")]
+    struct Disk {
+        pub used: u64,
+        pub free: Option<u64>,
+        pub inodes: NonZeroU32,
+        pub full: bool,
+        pub signed: i64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "disk", unsigned = "allow")]
+    struct Allowed {
+        pub used: u64,
+    }
+
+    let data = Disk { used: i64::MAX as u64, free: Some(0), inodes: NonZeroU32::new(7).unwrap(), full: false, signed: -1 };
+    assert_eq!(data.to_lp(), "disk used=9223372036854775807i,free=0i,inodes=7i,full=f,signed=-1i");
+    let data = Disk { used: i64::MAX as u64 + 1, free: None, inodes: NonZeroU32::MIN, full: true, signed: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("used") }));
+    let data = Disk { used: 1, free: Some(u64::MAX), inodes: NonZeroU32::MIN, full: true, signed: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("free") }));
+
+    assert_eq!(Allowed { used: u64::MAX }.to_lp(), "disk used=18446744073709551615u");
+
+    // read back from the `i` integers it writes
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "disk", unsigned = "forbid")]
+    struct Readable {
+        pub used: u64,
+        pub signed: i64,
+        #[influx3_lp(fields)]
+        pub parts: std::collections::BTreeMap<String, u32>,
+    }
+
+    let data = Readable { used: 3, signed: -1, parts: [(String::from("boot"), 5)].into() };
+    assert_eq!(data.to_lp(), "disk used=3i,signed=-1i,boot=5i");
+    assert_eq!(Readable::from_lp(&data.to_lp()), Ok(data));
+    assert!(Readable::from_lp("disk used=-3i,signed=-1i").is_err());
+
+    // a struct level len_unit only limits strings, the style of other fields is kept
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "disk", unsigned = "forbid", len_unit = "chars")]
+    struct Labeled {
+        pub used: u64,
+        pub label: String,
+    }
+
+    let data = Labeled { used: 5, label: String::from("é") };
+    assert_eq!(data.to_lp(), "disk used=5i,label=\"é\"");
+
+    // the values of a fields map are written like the named fields
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "disk", unsigned = "forbid")]
+    struct Mapped {
+        pub used: u64,
+        #[influx3_lp(fields)]
+        pub parts: std::collections::BTreeMap<String, u64>,
+    }
+
+    let data = Mapped { used: 3, parts: [(String::from("boot"), 5)].into() };
+    assert_eq!(data.to_lp(), "disk used=3i,boot=5i");
+    let data = Mapped { used: 3, parts: [(String::from("boot"), i64::MAX as u64 + 1)].into() };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("boot") }));
+}
+
+#[test]
+fn test_numeric_tags() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: i32,
+        #[influx3_lp(tag)]
+        pub temp_tag: i32,
+        #[influx3_lp(tag)]
+        pub zero: f64,
+        #[influx3_lp(tag)]
+        pub big: u64,
+        #[influx3_lp(tag)]
+        pub wide: i128,
+    }
+
+    // tags are strings, the `i` / `u` suffixes of integer fields never show up in them
+    let data = SensorData { temp: -5, temp_tag: -5, zero: -0.0, big: u64::MAX, wide: i128::MIN };
+    assert_eq!(data.to_lp(),
+               "home,temp_tag=-5,zero=-0,big=18446744073709551615,wide=-170141183460469231731687303715884105728 temp=-5i");
+    let data = SensorData { temp: 0, temp_tag: 0, zero: 0.0, big: 0, wide: 0 };
+    assert_eq!(data.to_lp(), "home,temp_tag=0,zero=0,big=0,wide=0 temp=0i");
+}
+
+#[test]
+fn test_ip_addresses() {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "conn")]
+    struct Connection {
+        pub peer: Ipv6Addr,
+        pub gateway: Option<IpAddr>,
+        #[influx3_lp(tag)]
+        pub host: Ipv6Addr,
+        #[influx3_lp(tag)]
+        pub local: Option<Ipv4Addr>,
+    }
+
+    let data = Connection {
+        peer: "2001:db8::1".parse().unwrap(),
+        gateway: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+        host: "fe80::1:2".parse().unwrap(),
+        local: Some(Ipv4Addr::LOCALHOST),
+    };
+    let line = "conn,host=fe80::1:2,local=127.0.0.1 peer=\"2001:db8::1\",gateway=\"10.0.0.1\"";
+    assert_eq!(data.to_lp(), line);
+    assert_eq!(Connection::from_lp(line), Ok(data));
+
+    let data = Connection { peer: Ipv6Addr::LOCALHOST, gateway: None, host: Ipv6Addr::UNSPECIFIED, local: None };
+    assert_eq!(data.to_lp(), "conn,host=:: peer=\"::1\"");
+}
+
+#[test]
+fn test_optional_borrowed_strings() {
+    use std::borrow::Cow;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData<'a> {
+        pub temp: f32,
+        pub weather: Option<&'a str>,
+        #[influx3_lp(field, max_len = 8)]
+        pub note: Option<Cow<'a, str>>,
+        #[influx3_lp(tag)]
+        pub room: Option<&'a str>,
+        #[influx3_lp(tag)]
+        pub city: Option<Cow<'a, str>>,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        weather: Some("x"),
+        note: Some(Cow::Borrowed("y")),
+        room: Some("x"),
+        city: Some(Cow::Owned(String::from("y"))),
+    };
+    assert_eq!(data.to_lp(), "home,room=x,city=y temp=21,weather=\"x\",note=\"y\"");
+
+    let data = SensorData { temp: 21.0, weather: None, note: None, room: None, city: None };
+    assert_eq!(data.to_lp(), "home temp=21");
+
+    let owned = String::from("New York, \"NY\"");
+    let data = SensorData {
+        temp: 21.0,
+        weather: Some(&owned),
+        note: Some(Cow::Borrowed("a\\b")),
+        room: Some(&owned),
+        city: Some(Cow::Borrowed("a=b")),
+    };
+    assert_eq!(data.to_lp(),
+               "home,room=New\\ York\\,\\ \"NY\",city=a\\=b temp=21,weather=\"New York, \\\"NY\\\"\",note=\"a\\\\b\"");
+}
+
+#[test]
+fn test_escaping_matches_line_builder() {
+    use influx3_lp::LineBuilder;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "my home,1")]
+    struct SensorData {
+        #[influx3_lp(field, name = "note\\ a,b=c")]
+        pub note: String,
+        #[influx3_lp(tag, name = "room\\ a,b=c")]
+        pub room: String,
+    }
+
+    // keys escaped by the macro and values escaped at runtime agree with `LineBuilder`,
+    // which escapes everything at runtime
+    for value in ["plain", "a\\b", "a,b c=d", "\"q\" \\\"", "x\\,\\ \\="] {
+        let data = SensorData { note: value.to_string(), room: value.to_string() };
+        let built = LineBuilder::new("my home,1")
+            .tag("room\\ a,b=c", value)
+            .field_str("note\\ a,b=c", value)
+            .build()
+            .unwrap();
+        assert_eq!(data.to_lp(), built);
+    }
+    let data = SensorData { note: String::from("n"), room: String::from("r") };
+    assert_eq!(data.to_lp(), "my\\ home\\,1,room\\\\\\ a\\,b\\=c=r note\\\\\\ a\\,b\\=c=\"n\"");
+}
+
+#[test]
+fn test_borrowed_tag_values() {
+    use std::borrow::Cow;
+    use std::fmt;
+    use influx3_lp::LpTagValue;
+
+    struct Zone(u8);
+
+    impl fmt::Display for Zone {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "zone-{}", self.0)
+        }
+    }
+
+    impl LpTagValue for Zone {
+        fn tag_value(&self) -> Cow<'_, str> {
+            Cow::Owned(self.to_string())
+        }
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData<'a> {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: &'a str,
+        #[influx3_lp(tag)]
+        pub city: Cow<'a, str>,
+        #[influx3_lp(tag)]
+        pub house: Option<Box<str>>,
+        #[influx3_lp(tag)]
+        pub zone: Zone,
+    }
+
+    let data = SensorData {
+        temp: 21.0,
+        room: "Living Room",
+        city: Cow::Borrowed("Paris"),
+        house: Some(Box::from("a=b")),
+        zone: Zone(3),
+    };
+    assert_eq!(data.to_lp(), "home,room=Living\\ Room,city=Paris,house=a\\=b,zone=zone-3 temp=21");
+}
+
+#[test]
+fn test_shared_strings() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub weather: Arc<str>,
+        pub note: Rc<str>,
+        pub source: Arc<String>,
+        #[influx3_lp(field, max_len = 4, on_overflow = "truncate")]
+        pub owner: Rc<String>,
+        #[influx3_lp(tag)]
+        pub room: Arc<str>,
+    }
+
+    let data = SensorData {
+        weather: Arc::from("sunny, \"warm\""),
+        note: Rc::from("a,b"),
+        source: Arc::new(String::from("x=y")),
+        owner: Rc::new(String::from("alice")),
+        room: Arc::from("Living Room"),
+    };
+    assert_eq!(data.to_lp(),
+               "home,room=Living\\ Room weather=\"sunny, \\\"warm\\\"\",note=\"a,b\",source=\"x=y\",owner=\"alic\"");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_to_lp_serde() {
+    use influx3_lp::{to_lp_serde, LpError};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Weather {
+        Sunny,
+    }
+
+    #[derive(Serialize)]
+    struct SensorData {
+        #[serde(rename = "temperature")]
+        pub temp: f64,
+        pub co: Option<i32>,
+        pub hum: Option<u8>,
+        pub weather: Weather,
+        pub note: String,
+        pub open: bool,
+        #[serde(skip)]
+        #[allow(dead_code)]
+        pub cache: Vec<u8>,
+    }
+
+    let data = SensorData {
+        temp: 21.5,
+        co: None,
+        hum: Some(35),
+        weather: Weather::Sunny,
+        note: String::from("say \"hi\""),
+        open: true,
+        cache: vec![1, 2],
+    };
+    assert_eq!(to_lp_serde("my home", &data).unwrap(),
+               "my\\ home temperature=21.5,hum=35u,weather=\"sunny\",note=\"say \\\"hi\\\"\",open=true");
+
+    #[derive(Serialize)]
+    struct Nested {
+        pub temp: f64,
+        pub readings: Vec<f64>,
+    }
+    assert!(matches!(to_lp_serde("home", &Nested { temp: 1.0, readings: vec![] }), Err(LpError::Serde(_))));
+    assert!(matches!(to_lp_serde("home", &1), Err(LpError::Serde(_))));
+
+    #[derive(Serialize)]
+    struct Empty {
+        pub co: Option<i32>,
+    }
+    assert_eq!(to_lp_serde("home", &Empty { co: None }), Err(LpError::EmptyFieldSet));
+    assert!(matches!(to_lp_serde("_home", &data), Err(LpError::InvalidMeasurement { .. })));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_line_builder_json() {
+    use influx3_lp::{LineBuilder, LpError};
+    use serde_json::json;
+
+    let payload = json!({ "temp": 21.5, "co": -3, "big": u64::MAX, "weather": "sunny", "open": true });
+    let line = LineBuilder::new("home")
+        .tag("room", "Kitchen")
+        .fields_json(payload.as_object().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(line, "home,room=Kitchen big=18446744073709551615u,co=-3i,open=true,temp=21.5,weather=\"sunny\"");
+
+    for value in [json!(null), json!([1, 2]), json!({ "a": 1 })] {
+        let result = LineBuilder::new("home").field_int("co", 1).field_json("bad", &value).build();
+        assert_eq!(result, Err(LpError::UnsupportedJson { field: String::from("bad") }));
+    }
+}
+
+#[test]
+fn test_wide_integers() {
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "counter")]
+    struct Counter {
+        pub signed: i128,
+        pub unsigned: u128,
+    }
+
+    let data = Counter { signed: i64::MIN as i128, unsigned: u64::MAX as u128 };
+    let line = data.try_to_lp().unwrap();
+    assert_eq!(line, "counter signed=-9223372036854775808i,unsigned=18446744073709551615u");
+    assert_eq!(Counter::from_lp(&line), Ok(data));
+
+    let data = Counter { signed: i64::MAX as i128 + 1, unsigned: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("signed") }));
+    let data = Counter { signed: i64::MIN as i128 - 1, unsigned: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("signed") }));
+    let data = Counter { signed: 0, unsigned: u64::MAX as u128 + 1 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("unsigned") }));
+}
+
+#[test]
+fn test_pointer_sized_integers() {
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "queue")]
+    struct Queue {
+        pub count: usize,
+        pub delta: isize,
+        pub peak: Option<usize>,
+        pub drift: Option<isize>,
+    }
+
+    let data = Queue { count: 5, delta: -2, peak: Some(9), drift: None };
+    assert_eq!(data.to_lp(), "queue count=5u,delta=-2i,peak=9u");
+    assert_eq!(Queue::from_lp(&data.to_lp()), Ok(data));
+}
+
+#[test]
+fn test_non_zero_integers() {
+    use std::num::{NonZeroI64, NonZeroU32};
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "device")]
+    struct Device {
+        pub id: NonZeroU32,
+        pub offset: Option<NonZeroI64>,
+    }
+
+    let data = Device { id: NonZeroU32::new(7).unwrap(), offset: NonZeroI64::new(-3) };
+    assert_eq!(data.to_lp(), "device id=7u,offset=-3i");
+    assert_eq!(Device::from_lp(&data.to_lp()), Ok(data));
+    assert!(Device::from_lp("device id=0u").is_err());
+}
+
+#[test]
+fn test_max_field_len() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log", max_field_len = 8)]
+    struct Log<'a> {
+        pub level: &'a str,
+        #[influx3_lp(field, max_len = 100_000)]
+        pub message: String,
+        pub code: Option<Box<str>>,
+        #[influx3_lp(max_len = 2)]
+        pub count: i64,
+    }
+
+    let data = Log { level: "warning", message: "x".repeat(70_000), code: Some(Box::from("E1")), count: 100 };
+    let line = data.try_to_lp().unwrap();
+    assert!(line.starts_with("log level=\"warning\",message=\"xxx"));
+    assert!(line.ends_with("\",code=\"E1\",count=100i"));
+
+    let data = Log { level: "critical!", message: String::new(), code: None, count: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("level") }));
+    let data = Log { level: "info", message: "x".repeat(100_001), code: None, count: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("message") }));
+    let data = Log { level: "info", message: String::new(), code: Some(Box::from("E123456789")), count: 0 };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("code") }));
+}
+
+#[test]
+fn test_len_unit() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log", max_field_len = 4)]
+    struct Log {
+        #[influx3_lp(len_unit = "chars")]
+        pub chars: String,
+        pub bytes: String,
+    }
+
+    // 4 chars but 8 bytes
+    let data = Log { chars: String::from("éééé"), bytes: String::from("abcd") };
+    assert_eq!(data.try_to_lp().unwrap(), "log chars=\"éééé\",bytes=\"abcd\"");
+    let data = Log { chars: String::from("ééééé"), bytes: String::from("abcd") };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("chars") }));
+    let data = Log { chars: String::new(), bytes: String::from("éé") };
+    assert_eq!(data.try_to_lp().unwrap(), "log chars=\"\",bytes=\"éé\"");
+    let data = Log { chars: String::new(), bytes: String::from("ééa") };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("bytes") }));
+
+    // the default 64K limit counted in chars
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log", len_unit = "chars")]
+    struct Message {
+        pub text: String,
+    }
+
+    assert!(Message { text: "é".repeat(64 * 1024) }.try_to_lp().is_ok());
+    assert_eq!(Message { text: "é".repeat(64 * 1024 + 1) }.try_to_lp(),
+               Err(LpError::FieldTooLong { field: String::from("text") }));
+}
+
+#[test]
+fn test_truncate_on_overflow() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log")]
+    struct Log {
+        #[influx3_lp(field, on_overflow = "truncate")]
+        pub message: String,
+        #[influx3_lp(field, max_len = 5, on_overflow = "truncate")]
+        pub short: Option<String>,
+        #[influx3_lp(field, max_len = 2, len_unit = "chars", on_overflow = "truncate")]
+        pub initials: String,
+    }
+
+    let data = Log { message: "x".repeat(70 * 1024), short: None, initials: String::from("ab") };
+    let line = data.to_lp();
+    assert_eq!(line, format!("log message=\"{}\",initials=\"ab\"", "x".repeat(64 * 1024)));
+
+    // cut on a char boundary, before escaping
+    let data = Log { message: String::new(), short: Some(String::from("ab\"cé")), initials: String::from("ééé") };
+    assert_eq!(data.to_lp(), "log message=\"\",short=\"ab\\\"c\",initials=\"éé\"");
+}
+
+#[test]
+fn test_multibyte_strings() {
+    use influx3_lp::{parse_line, FieldValue, LpError};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "chat")]
+    struct Message {
+        pub text: String,
+        #[influx3_lp(field, on_overflow = "truncate")]
+        pub preview: Option<String>,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    // 4 bytes each, 64K bytes in total
+    let emoji = "😀".repeat(16 * 1024);
+    let data = Message { text: emoji.clone(), preview: None, room: String::from("🎉") };
+    assert_eq!(data.try_to_lp().unwrap(), format!("chat,room=🎉 text=\"{}\"", emoji));
+    let data = Message { text: format!("a{}", emoji), preview: None, room: String::new() };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("text") }));
+
+    // the limit falls inside the last emoji, which is dropped whole
+    let data = Message { text: String::new(), preview: Some(format!("a{}", emoji)), room: String::new() };
+    let expected = format!("a{}", "😀".repeat(16 * 1024 - 1));
+    assert_eq!(data.to_lp(), format!("chat text=\"\",preview=\"{}\"", expected));
+
+    // special chars next to multibyte ones are escaped without splitting them
+    let text = "日本,語 \"引用\"\\😀=é";
+    let data = Message { text: String::from(text), preview: None, room: String::from(text) };
+    let line = data.to_lp();
+    assert_eq!(line, "chat,room=日本\\,語\\ \"引用\"\\\\😀\\=é text=\"日本,語 \\\"引用\\\"\\\\😀=é\"");
+    let parts = parse_line(&line).unwrap();
+    assert_eq!(parts.tags[0].1, text);
+    assert_eq!(parts.fields[0].1, FieldValue::String(String::from(text)));
+}
+
+#[test]
+fn test_cast_field_type() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "meter")]
+    struct Meter {
+        #[influx3_lp(field, as = "float")]
+        pub reading: i64,
+        #[influx3_lp(as = "float")]
+        pub big: Option<u64>,
+        #[influx3_lp(as = "integer")]
+        pub count: f64,
+        #[influx3_lp(as = "integer")]
+        pub total: u32,
+    }
+
+    let data = Meter { reading: -42, big: Some(u64::MAX), count: 7.0, total: 3 };
+    assert_eq!(data.to_lp(), "meter reading=-42,big=18446744073709551615,count=7i,total=3i");
+
+    let data = Meter { reading: 0, big: None, count: 7.5, total: 3 };
+    assert_eq!(data.try_to_lp(), Err(LpError::NotAnInteger { field: String::from("count") }));
+    let data = Meter { reading: 0, big: None, count: f64::NAN, total: 3 };
+    assert_eq!(data.try_to_lp(), Err(LpError::NotAnInteger { field: String::from("count") }));
+    let data = Meter { reading: 0, big: None, count: 1e19, total: 3 };
+    assert_eq!(data.try_to_lp(), Err(LpError::IntegerOutOfRange { field: String::from("count") }));
+    let data = Meter { reading: 0, big: None, count: -9_223_372_036_854_775_808.0, total: 3 };
+    assert_eq!(data.to_lp(), "meter reading=0,count=-9223372036854775808i,total=3i");
+}
+
+#[test]
+fn test_char_field() {
+    use influx3_lp::{FromLp, LpError};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "exam")]
+    struct Exam {
+        pub grade: char,
+        pub bonus: Option<char>,
+    }
+
+    let data = Exam { grade: 'A', bonus: Some('"') };
+    assert_eq!(data.to_lp(), "exam grade=\"A\",bonus=\"\\\"\"");
+    assert_eq!(Exam::from_lp(&data.to_lp()), Ok(data));
+    assert_eq!(Exam { grade: 'é', bonus: None }.to_lp(), "exam grade=\"é\"");
+    assert_eq!(Exam { grade: '\n', bonus: None }.try_to_lp(),
+               Err(LpError::InvalidFieldValue { field: String::from("grade") }));
+    assert!(Exam::from_lp("exam grade=\"AB\"").is_err());
+}
+
+#[test]
+fn test_measurement() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home temp")]
+    struct SensorData {
+        pub temp: f32,
+    }
+
+    #[derive(Influx3Lp)]
+    struct Metric {
+        #[influx3_lp(table_name)]
+        pub table: String,
+        pub value: f64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "events")]
+    enum Event<'a> {
+        #[influx3_lp(table_name = "door")]
+        Door { open: bool },
+        Custom {
+            #[influx3_lp(table_name)]
+            kind: &'a str,
+            count: u32,
+        },
+        Other { count: u32 },
+    }
+
+    assert_eq!(SensorData { temp: 21.0 }.measurement(), "home temp");
+    assert_eq!(Metric { table: String::from("cpu"), value: 0.5 }.measurement(), "cpu");
+    assert_eq!(Event::Door { open: true }.measurement(), "door");
+    assert_eq!(Event::Custom { kind: "window", count: 1 }.measurement(), "window");
+    assert_eq!(Event::Other { count: 1 }.measurement(), "events");
+}
+
+#[test]
+fn test_lp_len_hint() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "my home")]
+    struct SensorData {
+        pub temp: f64,
+        pub co: Option<i32>,
+        pub note: String,
+        #[influx3_lp(tag)]
+        pub room: String,
+        #[influx3_lp(tag)]
+        pub floor: u8,
+        #[influx3_lp(timestamp)]
+        pub timestamp: i64,
+    }
+
+    let data = SensorData {
+        temp: 21.125,
+        co: None,
+        note: String::from("say \"hé\""),
+        room: String::from("Living Room,1"),
+        floor: 2,
+        timestamp: 1735545600,
+    };
+    assert_eq!(data.lp_len_hint(), data.to_lp().len());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_write_lp_batch_to() {
+    use std::io::{self, Write};
+    use influx3_lp::{to_lp_batch, write_lp_batch_to};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f64,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let points = vec![
+        SensorData { temp: 21.0, room: String::from("Kitchen") },
+        SensorData { temp: 19.5, room: String::from("Bed Room") },
+    ];
+    let mut out: Vec<u8> = Vec::new();
+    write_lp_batch_to(&points, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), to_lp_batch(&points));
+
+    let mut out: Vec<u8> = Vec::new();
+    write_lp_batch_to::<SensorData, _>(&[], &mut out).unwrap();
+    assert!(out.is_empty());
+
+    // the first error stops the batch
+    struct Full(usize);
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 < buf.len() {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let err = write_lp_batch_to(&points, &mut Full(30)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+    let invalid = vec![SensorData { temp: f64::NAN, room: String::new() }, points.into_iter().next().unwrap()];
+    let mut out: Vec<u8> = Vec::new();
+    let err = write_lp_batch_to(&invalid, &mut out).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(!out.contains(&b'\n'));
+}
+
+#[test]
+fn test_write_lp_line() {
+    use influx3_lp::parse_line;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        pub temp: f32,
+        #[influx3_lp(tag)]
+        pub room: String,
+    }
+
+    let mut body = String::new();
+    SensorData { temp: 21.0, room: String::from("Kitchen") }.write_lp_line(&mut body);
+    SensorData { temp: 19.5, room: String::from("Bedroom") }.write_lp_line(&mut body);
+    assert_eq!(body, "home,room=Kitchen temp=21\nhome,room=Bedroom temp=19.5\n");
+    assert!(body.lines().all(|line| parse_line(line).is_ok()));
+
+    // a panicking write leaves the body as it was
+    let before = body.clone();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        SensorData { temp: f32::NAN, room: String::new() }.write_lp_line(&mut body);
+    }));
+    assert!(result.is_err());
+    assert_eq!(body, before);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_output_targets_agree() {
+    use std::io::Write;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "special data")]
+    struct SpecialData {
+        pub f1: String,
+        pub f2: Option<f64>,
+        #[influx3_lp(tag)]
+        pub t1: String,
+        #[influx3_lp(timestamp)]
+        pub timestamp: i64,
+    }
+
+    let data = SpecialData {
+        f1: String::from("quote \" back slash \\"),
+        f2: Some(0.1),
+        t1: String::from("with,comma =equal"),
+        timestamp: 1735545600,
+    };
+    let line = data.to_lp();
+
+    let mut out: Vec<u8> = Vec::new();
+    {
+        let w: &mut dyn Write = &mut out;
+        data.write_lp_to(&mut &mut *w).unwrap();
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), line);
+    assert_eq!(data.lp_len_hint(), line.len());
+}
+
+#[test]
+fn test_tuple_struct() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "point", names("x", "y", "sensor", "time"))]
+    struct Point(f64, f64, #[influx3_lp(tag)] String, #[influx3_lp(timestamp)] i64);
+
+    let point = Point(1.5, -2.0, String::from("gps"), 1735545600);
+    assert_eq!(point.to_lp(), "point,sensor=gps x=1.5,y=-2 1735545600");
+
+    // a member's own name wins over the positional one
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "point", names("x", "y"))]
+    struct Renamed(f64, #[influx3_lp(name = "lat")] f64);
+
+    assert_eq!(Renamed(1.0, 2.0).to_lp(), "point x=1,lat=2");
+
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/tuple_without_names.rs");
+}
+
+#[test]
+fn test_trim_tag() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(tag, trim)]
+        region: String,
+        #[influx3_lp(tag, trim)]
+        room: Option<&'static str>,
+        #[influx3_lp(tag)]
+        host: String,
+        temp: f64,
+    }
+
+    let data = SensorData {
+        region: String::from("  us-east  "),
+        room: Some("   "),
+        host: String::from(" a "),
+        temp: 21.0,
+    };
+    // a tag left empty by trimming is dropped, tags without trim keep their spaces
+    assert_eq!(data.to_lp(), "home,region=us-east,host=\\ a\\  temp=21");
+}
+
+#[test]
+fn test_escape_functions() {
+    use influx3_lp::{escape_field_key, escape_field_value, escape_measurement, escape_tag_key, escape_tag_value};
+    use influx3_lp::LineBuilder;
+    use std::borrow::Cow;
+
+    // nothing to escape, nothing allocated
+    assert!(matches!(escape_tag_value("us-east"), Cow::Borrowed("us-east")));
+    assert!(matches!(escape_field_value("plain text"), Cow::Borrowed("plain text")));
+
+    // a line assembled by hand matches the one of `LineBuilder`
+    for value in ["plain", "a\\b", "a,b c=d", "\"q\" \\\"", "x\\,\\ \\="] {
+        let by_hand = format!("{},{}={} {}=\"{}\"",
+                              escape_measurement("my home,1"),
+                              escape_tag_key("room a"),
+                              escape_tag_value(value),
+                              escape_field_key("note=b"),
+                              escape_field_value(value));
+        let built = LineBuilder::new("my home,1")
+            .tag("room a", value)
+            .field_str("note=b", value)
+            .build()
+            .unwrap();
+        assert_eq!(by_hand, built);
+    }
+}
+
+#[test]
+fn test_skip_if() {
+    fn no_reading(v: &i32) -> bool {
+        *v == i32::MIN
+    }
+
+    mod sentinel {
+        pub fn is_zero(v: &Option<f64>) -> bool {
+            *v == Some(0.0)
+        }
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(skip_if = "no_reading")]
+        co: i32,
+        #[influx3_lp(skip_if = "sentinel::is_zero")]
+        hum: Option<f64>,
+        temp: f64,
+    }
+
+    let data = SensorData { co: 3, hum: Some(35.5), temp: 21.0 };
+    assert_eq!(data.to_lp(), "home co=3i,hum=35.5,temp=21");
+
+    let data = SensorData { co: i32::MIN, hum: Some(0.0), temp: 21.0 };
+    assert_eq!(data.to_lp(), "home temp=21");
+
+    let data = SensorData { co: i32::MIN, hum: None, temp: 21.0 };
+    assert_eq!(data.to_lp(), "home temp=21");
+}
+
+#[test]
+fn test_default_for_none() {
+    fn unknown() -> String {
+        String::from("unknown")
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(default = 0)]
+        count: Option<i64>,
+        #[influx3_lp(default = "unknown")]
+        weather: Option<String>,
+        #[influx3_lp(default = -1.5)]
+        temp: Option<Option<f64>>,
+    }
+
+    let data = SensorData { count: None, weather: None, temp: Some(None) };
+    assert_eq!(data.to_lp(), "home count=0i,weather=\"unknown\",temp=-1.5");
+
+    let data = SensorData { count: Some(3), weather: Some(String::from("sunny")), temp: Some(Some(21.0)) };
+    assert_eq!(data.to_lp(), "home count=3i,weather=\"sunny\",temp=21");
+}
+
+#[test]
+fn test_explode() {
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(tag)]
+        room: String,
+        #[influx3_lp(explode, name = "reading")]
+        readings: Vec<f64>,
+        co: i32,
+        #[influx3_lp(timestamp)]
+        timestamp: i64,
+    }
+
+    let data = SensorData { room: String::from("Kitchen"), readings: vec![21.0, 21.5, 22.0], co: 1, timestamp: 1735545600 };
+    let lp = data.to_lp();
+    assert_eq!(lp.lines().count(), 3);
+    assert_eq!(lp,
+               "home,room=Kitchen co=1i,reading=21 1735545600\n\
+                home,room=Kitchen co=1i,reading=21.5 1735545600\n\
+                home,room=Kitchen co=1i,reading=22 1735545600");
+    assert_eq!(data.lp_len_hint(), lp.len());
+    assert_eq!(<SensorData as influx3_lp::LpColumnCounts>::FIELD_COUNT, 2);
+
+    let empty = SensorData { room: String::from("Kitchen"), readings: Vec::new(), co: 1, timestamp: 1735545600 };
+    assert!(matches!(empty.try_to_lp(), Err(influx3_lp::LpError::EmptyFieldSet)));
+
+    // the only field
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Readings {
+        #[influx3_lp(explode)]
+        temp: Vec<f32>,
+    }
+
+    assert_eq!(Readings { temp: vec![1.0, 2.0] }.to_lp(), "home temp=1\nhome temp=2");
+}
+
+#[test]
+fn test_tags_map() {
+    use influx3_lp::FromLp;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(tag)]
+        room: String,
+        #[influx3_lp(tags)]
+        labels: BTreeMap<String, String>,
+        temp: f64,
+    }
+
+    let labels = BTreeMap::from([
+        (String::from("region"), String::from("us east")),
+        (String::from("host"), String::from("a")),
+        (String::from("rack"), String::new()),
+    ]);
+    let data = SensorData { room: String::from("Kitchen"), labels, temp: 21.0 };
+    let lp = data.to_lp();
+    assert_eq!(lp, "home,room=Kitchen,host=a,region=us\\ east temp=21");
+
+    // the empty value isn't read back
+    let mut expected = data;
+    expected.labels.remove("rack");
+    assert_eq!(SensorData::from_lp(&lp).unwrap(), expected);
+
+    // entries of a HashMap are sorted
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Unordered {
+        #[influx3_lp(tags)]
+        labels: HashMap<&'static str, u32>,
+        temp: f64,
+    }
+
+    let data = Unordered { labels: HashMap::from([("zone", 3), ("floor", 1), ("desk", 12)]), temp: 21.0 };
+    assert_eq!(data.to_lp(), "home,desk=12,floor=1,zone=3 temp=21");
+}
+
+#[test]
+fn test_to_lp_cow() {
+    use std::borrow::Cow;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(tag)]
+        room: String,
+        weather: String,
+        temp: f64,
+    }
+
+    let data = SensorData { room: String::from("Living Room"), weather: String::from("sunny, \"warm\""), temp: 21.5 };
+    let line = data.to_lp_cow();
+    assert_eq!(line, data.to_lp());
+    // exactly sized, so never larger than the `String` of `to_lp`
+    match line {
+        Cow::Owned(s) => assert_eq!(s.capacity(), s.len()),
+        Cow::Borrowed(_) => panic!("a line is always built"),
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_system_time_timestamp() {
+    use influx3_lp::LpError;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        temp: f64,
+        #[influx3_lp(timestamp)]
+        time: SystemTime,
+    }
+
+    let time = UNIX_EPOCH + Duration::from_secs(1735545600);
+    assert_eq!(SensorData { temp: 21.0, time }.to_lp(), "home temp=21 1735545600000000000");
+
+    let time = UNIX_EPOCH - Duration::from_secs(1);
+    assert_eq!(SensorData { temp: 21.0, time }.try_to_lp(), Err(LpError::InvalidTimestamp));
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Optional {
+        temp: f64,
+        #[influx3_lp(timestamp)]
+        time: Option<SystemTime>,
+    }
+
+    let time = Some(UNIX_EPOCH + Duration::from_nanos(1_735_545_600_123_456_789));
+    assert_eq!(Optional { temp: 21.0, time }.to_lp(), "home temp=21 1735545600123456789");
+    assert_eq!(Optional { temp: 21.0, time: None }.to_lp(), "home temp=21");
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn test_index_map_order() {
+    use indexmap::IndexMap;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        #[influx3_lp(tags)]
+        labels: IndexMap<String, String>,
+        #[influx3_lp(fields)]
+        readings: IndexMap<&'static str, f64>,
+    }
+
+    let mut labels = IndexMap::new();
+    labels.insert(String::from("zone"), String::from("b"));
+    labels.insert(String::from("floor"), String::from("1"));
+    let mut readings = IndexMap::new();
+    readings.insert("temp", 21.0);
+    readings.insert("hum", 35.5);
+    readings.insert("co", 0.5);
+
+    // written in insertion order, not sorted
+    let data = SensorData { labels, readings };
+    assert_eq!(data.to_lp(), "home,zone=b,floor=1 temp=21,hum=35.5,co=0.5");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_timestamp_precision() {
+    use influx3_lp::Precision;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    assert_eq!(Precision::Seconds.as_query_value(), "second");
+    assert_eq!(Precision::Millis.as_query_value(), "millisecond");
+    assert_eq!(Precision::Micros.as_query_value(), "microsecond");
+    assert_eq!(Precision::Nanos.as_query_value(), "nanosecond");
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Millis {
+        temp: f64,
+        #[influx3_lp(timestamp, precision = "ms")]
+        time: SystemTime,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Seconds {
+        temp: f64,
+        #[influx3_lp(timestamp, precision = "s")]
+        time: Option<u32>,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct Unset {
+        temp: f64,
+    }
+
+    assert_eq!(Millis::lp_precision(), Precision::Millis);
+    assert_eq!(Seconds::lp_precision(), Precision::Seconds);
+    assert_eq!(Unset::lp_precision(), Precision::Nanos);
+
+    // a `SystemTime` is written in the unit, an integer as it is
+    let time = UNIX_EPOCH + Duration::from_nanos(1_735_545_600_123_456_789);
+    assert_eq!(Millis { temp: 21.0, time }.to_lp(), "home temp=21 1735545600123");
+    assert_eq!(Seconds { temp: 21.0, time: Some(1735545600) }.to_lp(), "home temp=21 1735545600");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_lp_writer() {
+    use influx3_lp::LpWriter;
+    use std::io::{self, Write};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        temp: f64,
+    }
+
+    // records every batch, `write_all` calls `write` once per batch
+    #[derive(Default)]
+    struct Batches(Vec<String>);
+
+    impl Write for Batches {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(String::from_utf8(buf.to_vec()).unwrap());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // "home temp=2X\n" is 13 bytes, two lines fit in 30
+    let mut writer = LpWriter::new(Batches::default(), 30);
+    for temp in [21.0, 22.0, 23.0, 24.0, 25.0] {
+        writer.push(&SensorData { temp }).unwrap();
+    }
+    assert_eq!(writer.buffered_len(), 13);
+
+    // an invalid point is left out, the batch is kept
+    let err = writer.push(&SensorData { temp: f64::NAN }).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let batches = writer.into_inner().unwrap().0;
+    assert_eq!(batches, ["home temp=21\nhome temp=22\n", "home temp=23\nhome temp=24\n", "home temp=25\n"]);
+    assert!(batches.iter().all(|b| b.len() <= 30));
+
+    // a line over the limit is a batch of its own
+    let mut writer = LpWriter::new(Batches::default(), 10);
+    writer.push(&SensorData { temp: 21.0 }).unwrap();
+    writer.push(&SensorData { temp: 22.0 }).unwrap();
+    assert_eq!(writer.buffered_len(), 0);
+    assert_eq!(writer.into_inner().unwrap().0, ["home temp=21\n", "home temp=22\n"]);
+}
+
+#[test]
+fn test_table_name_const() {
+    use influx3_lp::FromLp;
+
+    const HOME: &str = "living room";
+
+    mod names {
+        pub const KITCHEN: &str = "kitchen";
+    }
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = HOME)]
+    struct SensorData {
+        #[influx3_lp(tag)]
+        room: String,
+        temp: f64,
+    }
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = names::KITCHEN)]
+    enum Reading {
+        Temp { temp: f64 },
+        #[influx3_lp(table_name = "humidity")]
+        Hum { hum: f64 },
+    }
+
+    let data = SensorData { room: "den".to_string(), temp: 21.5 };
+    assert_eq!(data.to_lp(), "living\\ room,room=den temp=21.5");
+    assert_eq!(data.measurement(), "living room");
+    assert_eq!(SensorData::from_lp("living\\ room,room=den temp=21.5").unwrap(), data);
+    assert!(SensorData::from_lp("home,room=den temp=21.5").is_err());
+
+    assert_eq!(Reading::Temp { temp: 21.5 }.to_lp(), "kitchen temp=21.5");
+    assert_eq!(Reading::Hum { hum: 40.0 }.to_lp(), "humidity hum=40");
+}
+
+#[test]
+fn test_negative_zero_and_subnormal_floats() {
+    use influx3_lp::{parse_line, FieldValue};
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "home")]
+    struct SensorData {
+        temp: f64,
+        hum: f32,
+        #[influx3_lp(precision = 2)]
+        co: f64,
+        #[influx3_lp(as = "float")]
+        level: f64,
+    }
+
+    // -0.0 keeps its sign wherever a float is written, and reads back as -0.0
+    let data = SensorData { temp: -0.0, hum: -0.0, co: -0.0, level: -0.0 };
+    let line = data.to_lp();
+    assert_eq!(line, "home temp=-0,hum=-0,co=-0.00,level=-0");
+    let FieldValue::Float(temp) = parse_line(&line).unwrap().fields[0].1 else { panic!("temp isn't a float") };
+    assert!(temp == 0.0 && temp.is_sign_negative());
+    let data = SensorData { temp: 0.0, hum: 0.0, co: 0.0, level: 0.0 };
+    assert_eq!(data.to_lp(), "home temp=0,hum=0,co=0.00,level=0");
+
+    // the smallest subnormals are written in full, and read back exactly
+    let data = SensorData { temp: -f64::from_bits(1), hum: f32::from_bits(1), co: 1.0, level: 1.0 };
+    let line = data.to_lp();
+    let values = line.strip_prefix("home temp=").unwrap().split(',').collect::<Vec<_>>();
+    assert!(values[0].starts_with("-0.0000") && values[0].ends_with("0005"));
+    assert!(values[1].starts_with("hum=0.0000") && values[1].ends_with("0001"));
+    assert!(!values[0].contains(['e', 'E']) && !values[1].contains(['e', 'E']));
+    let fields = parse_line(&line).unwrap().fields;
+    assert_eq!(fields[0].1, FieldValue::Float(-f64::from_bits(1)));
+    // an f32 is written with the shortest digits reading back as the same f32
+    let FieldValue::Float(hum) = fields[1].1 else { panic!("hum isn't a float") };
+    assert_eq!(hum as f32, f32::from_bits(1));
+}
+
+#[test]
+fn test_no_len_check() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "log", max_field_len = 8)]
+    struct Log {
+        #[influx3_lp(field, no_len_check)]
+        pub status: String,
+        pub message: String,
+    }
+
+    // the status skips both the struct limit and the 64K default, which InfluxDB would reject
+    let data = Log { status: "x".repeat(70_000), message: String::from("ok") };
+    let line = data.try_to_lp().unwrap();
+    assert!(line.starts_with("log status=\"xxx"));
+    assert!(line.ends_with("\",message=\"ok\""));
+
+    // other fields keep their limit, and the status still can't hold a newline
+    let data = Log { status: String::from("ready"), message: String::from("too long!") };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("message") }));
+    let data = Log { status: String::from("a\nb"), message: String::new() };
+    assert_eq!(data.try_to_lp(), Err(LpError::InvalidFieldValue { field: String::from("status") }));
+}
+
+#[test]
+fn test_unsupported_field_type() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/fail/unsupported_field_type.rs");
+    t.compile_fail("tests/fail/unsupported_field_map.rs");
+    t.compile_fail("tests/fail/unsupported_field_struct.rs");
+}
+
+/// xorshift64*, a fixed seed keeps the generated cases the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.next() as usize % items.len()]
+    }
+
+    /// A string of up to 8 chars, mostly ones line protocol escapes.
+    fn string(&mut self) -> String {
+        let len = self.next() % 9;
+        (0..len).map(|_| self.pick(&['a', 'Z', '0', ' ', ',', '=', '"', '\\', '\'', 'é', '℃', '🌡'])).collect()
+    }
+
+    fn float(&mut self) -> f64 {
+        match self.next() % 4 {
+            0 => self.pick(&[0.0, -0.0, f64::MIN, f64::MAX, f64::MIN_POSITIVE, f64::EPSILON, f64::from_bits(1)]),
+            // any finite bit pattern, subnormals included
+            _ => Some(f64::from_bits(self.next())).filter(|v| v.is_finite()).unwrap_or(1.5),
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_generated() {
+    use influx3_lp::FromLp;
+
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    struct SensorData {
+        #[influx3_lp(table_name)]
+        measurement: String,
+        #[influx3_lp(tag)]
+        room: String,
+        #[influx3_lp(tag)]
+        wing: Option<String>,
+        temp: f64,
+        hum: f32,
+        co: i64,
+        level: u64,
+        on: bool,
+        note: String,
+        label: Option<String>,
+        #[influx3_lp(timestamp)]
+        timestamp: i64,
+    }
+
+    let mut rng = Rng(0x5eed_1f0f_0c0f_fee5);
+    for _ in 0..5_000 {
+        let data = SensorData {
+            // a measurement can't be empty, and the alphabet has no `_` to start one with
+            measurement: Some(rng.string()).filter(|s| !s.is_empty()).unwrap_or_else(|| String::from("home")),
+            // an empty tag value is left out, so it can't come back as an empty string
+            room: Some(rng.string()).filter(|s| !s.is_empty()).unwrap_or_else(|| String::from("a")),
+            wing: Some(rng.string()).filter(|s| !s.is_empty()),
+            temp: rng.float(),
+            // a large f64 overflows f32
+            hum: Some(rng.float() as f32).filter(|v| v.is_finite()).unwrap_or(f32::MAX),
+            co: { let any = rng.next() as i64; rng.pick(&[i64::MIN, -1, 0, i64::MAX, any]) },
+            level: { let any = rng.next(); rng.pick(&[0, u64::MAX, any]) },
+            on: rng.next().is_multiple_of(2),
+            note: rng.string(),
+            label: Some(rng.string()).filter(|_| rng.next().is_multiple_of(2)),
+            timestamp: { let any = rng.next() as i64; rng.pick(&[i64::MIN, 0, i64::MAX, any]) },
+        };
+        let line = data.to_lp();
+        let parsed = SensorData::from_lp(&line).unwrap_or_else(|e| panic!("{:?} doesn't read back: {}", line, e));
+        // -0.0 == 0.0, so the sign is compared on its own
+        assert_eq!(parsed.temp.is_sign_negative(), data.temp.is_sign_negative(), "{:?}", line);
+        assert_eq!(parsed, data, "{:?}", line);
+    }
+
+    // a backslash before a special char of the measurement is escaped too
+    let data = SensorData {
+        measurement: String::from("a\\,b\\ c\\"),
+        room: String::from("a"),
+        wing: None,
+        temp: 1.0,
+        hum: 1.0,
+        co: 0,
+        level: 0,
+        on: true,
+        note: String::new(),
+        label: None,
+        timestamp: 0,
+    };
+    assert!(data.to_lp().starts_with("a\\\\\\,b\\\\\\ c\\\\,room=a "), "{:?}", data.to_lp());
+    assert_eq!(SensorData::from_lp(&data.to_lp()), Ok(data));
+}
+
+#[test]
+fn test_max_field_len_with_style() {
+    use influx3_lp::LpError;
+
+    #[derive(Influx3Lp)]
+    #[influx3_lp(table_name = "a", max_field_len = 4, bool_format = "short", unsigned = "forbid",
+                 coalesce(primary, fallback))]
+    struct Status {
+        pub ok: bool,
+        pub n: u64,
+        pub note: String,
+        pub primary: Option<bool>,
+        pub fallback: Option<bool>,
+    }
+
+    let data = Status { ok: true, n: 5, note: String::from("fine"), primary: None, fallback: Some(false) };
+    assert_eq!(data.to_lp(), "a ok=t,n=5i,note=\"fine\",primary=f");
+    let data = Status { note: String::from("broken"), ..data };
+    assert_eq!(data.try_to_lp(), Err(LpError::FieldTooLong { field: String::from("note") }));
+}
+
+#[test]
+fn test_display_tag() {
+    use influx3_lp::FromLp;
+    use std::net::SocketAddr;
+
+    // SocketAddr doesn't implement LpTagValue, like any foreign type
+    #[derive(Influx3Lp, FromLp, Debug, PartialEq)]
+    #[influx3_lp(table_name = "conn")]
+    struct Conn {
+        pub open: u32,
+        #[influx3_lp(tag, display)]
+        pub peer: SocketAddr,
+        #[influx3_lp(tag, display)]
+        pub proxy: Option<SocketAddr>,
+    }
+
+    let data = Conn { open: 3, peer: "10.0.0.1:443".parse().unwrap(), proxy: None };
+    assert_eq!(data.to_lp(), "conn,peer=10.0.0.1:443 open=3u");
+    assert_eq!(Conn::from_lp(&data.to_lp()), Ok(data));
+
+    let data = Conn { open: 1, peer: "[::1]:80".parse().unwrap(), proxy: Some("10.0.0.2:3128".parse().unwrap()) };
+    assert_eq!(data.to_lp(), "conn,peer=[::1]:80,proxy=10.0.0.2:3128 open=1u");
+    assert_eq!(Conn::from_lp(&data.to_lp()), Ok(data));
+}